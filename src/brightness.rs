@@ -0,0 +1,171 @@
+use crate::{
+    channels::{pulse_registers, snap_min_on},
+    hal,
+    math::powf,
+    register_access::CHANNEL_COUNT,
+    Channel, Error, Pca9685,
+};
+
+/// Build a table converting 8-bit brightness levels into pulse lengths.
+///
+/// Entry `l` is `(l / 255) ^ gamma` scaled to the range `[0-4096]`, where
+/// 4096 stands for always on. A `gamma` of 1.0 gives a linear table and a
+/// typical value for LEDs is 2.2. `gamma` should be positive. Entries
+/// exceeding 4096 due to an invalid `gamma` are limited to 4096.
+///
+/// The table can be computed once, or even stored as a constant, and then
+/// be passed to [`Pca9685::set_all_channels_brightness_lut()`]. This keeps
+/// the costly exponentiation out of frequent updates.
+pub fn build_gamma_lut(gamma: f32) -> [u16; 256] {
+    let mut lut = [0; 256];
+    for (level, value) in lut.iter_mut().enumerate() {
+        *value = level_to_counts(level as u8, Some(gamma)).min(4096);
+    }
+    lut
+}
+
+impl<I2C, E> Pca9685<I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    /// Set the brightness of every channel by looking up its level in a table.
+    ///
+    /// `levels[n]` is the brightness level of channel `n` and `lut` converts
+    /// the levels into pulse lengths in the range `[0-4096]`, as returned by
+    /// [`build_gamma_lut()`]. A pulse length of 0 sets the `full OFF` flag and
+    /// 4096 sets the `full ON` flag. The pulses start at the `ON` offset of
+    /// each channel, see `set_channel_on_offset()`.
+    ///
+    /// All channels are written in a single transaction.
+    ///
+    /// Returns `Error::InvalidInputData` if a used table entry is greater than 4096.
+    pub fn set_all_channels_brightness_lut(
+        &mut self,
+        levels: &[u8; 16],
+        lut: &[u16; 256],
+    ) -> Result<(), Error<E>> {
+        let mut values = [(0, 0); CHANNEL_COUNT];
+        for (i, (value, level)) in values.iter_mut().zip(levels).enumerate() {
+            let counts = lut[usize::from(*level)];
+            if counts > 4096 {
+                return Err(Error::InvalidInputData);
+            }
+            *value = pulse_registers(self.on_offsets[i], snap_min_on(counts, self.min_on[i]));
+        }
+        self.write_channels_from(Channel::C0, &values)
+    }
+
+    /// Set the pulse lengths of all channels limiting their sum.
+    ///
+    /// `desired[n]` is the pulse length of channel `n` in the range
+    /// `[0-4096]`, where 0 sets the `full OFF` flag and 4096 sets the
+    /// `full ON` flag, as in `set_channel_simple()`. If the sum of all pulse
+    /// lengths exceeds `max_sum`, every pulse length is scaled down by the
+    /// factor `max_sum / sum`, for example to keep the total LED current
+    /// within the capacity of the power supply. The scaling uses integer
+    /// math and rounds down, so the sum of the written pulse lengths never
+    /// exceeds `max_sum`. For the same reason, pulses shorter than the
+    /// minimum set with `set_channel_min_on()` are always snapped down to 0.
+    ///
+    /// All channels are written in a single transaction. Returns
+    /// `Error::InvalidInputData` if any desired pulse length is greater than 4096.
+    pub fn set_channels_budgeted(
+        &mut self,
+        desired: &[u16; 16],
+        max_sum: u32,
+    ) -> Result<(), Error<E>> {
+        if desired.iter().any(|counts| *counts > 4096) {
+            return Err(Error::InvalidInputData);
+        }
+        let sum: u32 = desired.iter().map(|counts| u32::from(*counts)).sum();
+        let mut values = [(0, 0); CHANNEL_COUNT];
+        for (i, (value, counts)) in values.iter_mut().zip(desired).enumerate() {
+            let counts = if sum > max_sum {
+                // The result is at most `counts` so it fits.
+                (u64::from(*counts) * u64::from(max_sum) / u64::from(sum)) as u16
+            } else {
+                *counts
+            };
+            let counts = if counts < self.min_on[i] { 0 } else { counts };
+            *value = pulse_registers(self.on_offsets[i], counts);
+        }
+        self.write_channels_from(Channel::C0, &values)
+    }
+
+    /// Set the `OFF` counter of a channel to a sample of a stored curve.
+    ///
+    /// This writes `samples[index]` with `set_channel_off()`, which makes it
+    /// easy to step through animation keyframes stored, for example, in flash.
+    /// Indices past the end of `samples` wrap around so a curve can be
+    /// repeated by simply incrementing the index: `index` 5 of a curve with 4
+    /// samples selects `samples[1]`.
+    ///
+    /// Returns `Error::InvalidInputData` if `samples` is empty or if the
+    /// selected sample is greater than 4095.
+    pub fn apply_curve(
+        &mut self,
+        channel: Channel,
+        samples: &[u16],
+        index: usize,
+    ) -> Result<(), Error<E>> {
+        if samples.is_empty() {
+            return Err(Error::InvalidInputData);
+        }
+        self.set_channel_off(channel, samples[index % samples.len()])
+    }
+}
+
+/// Convert an 8-bit level into a pulse length in the range `[0-4096]`.
+pub(crate) fn level_to_counts(level: u8, gamma: Option<f32>) -> u16 {
+    let fraction = f32::from(level) / 255.0;
+    let fraction = match gamma {
+        Some(gamma) => powf(fraction, gamma),
+        None => fraction,
+    };
+    // `round()` is not available in `core`. The value is always positive here.
+    (fraction * 4096.0 + 0.5) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_extremes() {
+        assert_eq!(0, level_to_counts(0, None));
+        assert_eq!(4096, level_to_counts(255, None));
+        assert_eq!(0, level_to_counts(0, Some(2.2)));
+        assert_eq!(4096, level_to_counts(255, Some(2.2)));
+    }
+
+    #[test]
+    fn level_without_gamma_is_linear() {
+        assert_eq!(2056, level_to_counts(128, None));
+    }
+
+    #[test]
+    fn gamma_lut() {
+        let lut = build_gamma_lut(2.2);
+        assert_eq!(0, lut[0]);
+        assert_eq!(899, lut[128]);
+        assert_eq!(4096, lut[255]);
+        assert!(lut.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn linear_lut() {
+        let lut = build_gamma_lut(1.0);
+        assert_eq!(2056, lut[128]);
+    }
+
+    #[test]
+    fn invalid_gamma_lut_is_limited() {
+        assert!(build_gamma_lut(-1.0).iter().all(|value| *value <= 4096));
+    }
+
+    #[test]
+    fn level_with_gamma() {
+        // (128 / 255) ^ 2.2 * 4096 = 899.15
+        assert_eq!(899, level_to_counts(128, Some(2.2)));
+    }
+}
@@ -1,16 +1,35 @@
-use crate::{hal, Channel, Error, Pca9685, Register};
+use crate::{
+    config::BitFlagMode2,
+    hal::{self, blocking::delay::DelayMs},
+    register_access::{
+        channel_off_register, channel_on_register, CHANNEL_BLOCK_LEN, CHANNEL_COUNT,
+        CHANNEL_REGISTERS,
+    },
+    Channel, ChannelRegisters, Error, FlagState, Pca9685, PulseResult, RawCounter, Register,
+    RESOLUTION_BITS,
+};
+use core::convert::TryFrom;
 
 impl<I2C, E> Pca9685<I2C>
 where
     I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
 {
     /// Set double register to specific value not touching `full ON/OFF` flag.
+    ///
+    /// The `full ON/OFF` flag is bit 4 of the high register, that is, bit 12 of
+    /// the double register. Only 12-bit counter values `[0-4095]` are accepted
+    /// so the value itself can never set the flag. For example, `4096` (`0x1000`)
+    /// is rejected instead of silently turning the channel fully on/off.
+    ///
+    /// The current flag is read back from the device and preserved. The
+    /// reserved bits 13-15 are always written as 0.
     fn set_double_register_without_flag(&mut self, register: u8, value: u16) -> Result<(), Error<E>> {
         if value > 4095 {
             return Err(Error::InvalidInputData);
         }
         let reg_h = self.read_register(register + 1)?;
-        let value = ((reg_h & 0x10) as u16) << 8 | value;
+        // Only the flag is taken from the device and only the counter from `value`.
+        let value = ((reg_h & 0x10) as u16) << 8 | (value & 0x0FFF);
         self.write_double_register(register, value)
     }
 
@@ -22,16 +41,701 @@ where
     /// See section 7.3.3 "LED output and PWM control" of the datasheet for
     /// further details.
     pub fn set_channel_on(&mut self, channel: Channel, value: u16) -> Result<(), Error<E>> {
-        let reg = get_register_on(channel);
+        let reg = self.register_on(channel);
         self.set_double_register_without_flag(reg, value)
     }
 
     /// Set the `OFF` counter for the selected channel.
     ///
     /// This method does not touch `full OFF` flag.
+    ///
+    /// If deduplication is enabled with `set_dedup()`, nothing is written when
+    /// the value is the same as the one last written by this method.
     pub fn set_channel_off(&mut self, channel: Channel, value: u16) -> Result<(), Error<E>> {
-        let reg = get_register_off(channel);
-        self.set_double_register_without_flag(reg, value)
+        let physical = self.physical_channel(channel);
+        if self.dedup && physical != Channel::All && self.last_off[physical as usize] == Some(value)
+        {
+            return Ok(());
+        }
+        let reg = get_register_off(physical);
+        self.set_double_register_without_flag(reg, value)?;
+        if self.dedup && physical != Channel::All {
+            self.last_off[physical as usize] = Some(value);
+        }
+        Ok(())
+    }
+
+    /// Enable or disable skipping `set_channel_off()` writes of unchanged values.
+    ///
+    /// When enabled, the last value written by `set_channel_off()` is
+    /// remembered for each channel and writing the same value again does not
+    /// cause any bus traffic. Any other write to the `OFF` registers of a
+    /// channel, for example through `set_channel_on_off()`, `Channel::All`
+    /// or a failed write, makes the driver forget the value. Changes made to
+    /// the device by someone else are not detected, though.
+    ///
+    /// This also makes `push_frame()` write only the channels that changed
+    /// since the last frame.
+    ///
+    /// This is disabled by default. Changing the setting forgets all values.
+    pub fn set_dedup(&mut self, enabled: bool) {
+        self.dedup = enabled;
+        self.last_off = [None; CHANNEL_COUNT];
+        self.last_frame = None;
+    }
+
+    /// Enable or disable rejecting channel writes while the device is asleep.
+    ///
+    /// While the device is asleep (after `disable()` or at power-on until
+    /// `enable()`), the channel registers accept writes, but the outputs do
+    /// not follow them until the oscillator runs again. If enabled, any
+    /// method writing to the channel registers returns `Error::DeviceAsleep`
+    /// without writing the channels instead, which helps to find missing calls
+    /// to `enable()` in power-managed designs. The sleep state is taken from
+    /// the `MODE1` value known to the driver, so no additional transfer is
+    /// needed.
+    ///
+    /// `reset_to_defaults()` still writes the channels. This is disabled by
+    /// default.
+    pub fn set_strict_sleep(&mut self, enabled: bool) {
+        self.strict_sleep = enabled;
+    }
+
+    /// Set the `ON` and `OFF` counters for the selected channel.
+    ///
+    /// This clears both `full ON` and `full OFF` flags.
+    ///
+    /// Note that the full off setting takes precedence over the `on` settings.
+    /// See section 7.3.3 "LED output and PWM control" of the datasheet for
+    /// further details.
+    ///
+    /// The datasheet requires the `ON` and `OFF` counters to be different and
+    /// does not specify the output in that case. Equal values are written
+    /// anyway. Use `set_channel_on_off_strict()` to reject them instead.
+    pub fn set_channel_on_off(
+        &mut self,
+        channel: Channel,
+        on: u16,
+        off: u16,
+    ) -> Result<(), Error<E>> {
+        if on > 4095 || off > 4095 {
+            return Err(Error::InvalidInputData);
+        }
+        let reg = self.register_on(channel);
+        self.write_two_double_registers(reg, on, off)
+    }
+
+    /// Set the `ON` and `OFF` counters for the selected channel, rejecting
+    /// equal values.
+    ///
+    /// This behaves like `set_channel_on_off()` but returns
+    /// `Error::InvalidInputData` if `on` and `off` are equal, since the
+    /// datasheet does not allow that.
+    pub fn set_channel_on_off_strict(
+        &mut self,
+        channel: Channel,
+        on: u16,
+        off: u16,
+    ) -> Result<(), Error<E>> {
+        if on == off {
+            return Err(Error::InvalidInputData);
+        }
+        self.set_channel_on_off(channel, on, off)
+    }
+
+    /// Set the brightness of the selected channel as raw 12-bit value.
+    ///
+    /// This writes `ON = 0` and `OFF = value` and clears both `full ON` and
+    /// `full OFF` flags in a single transaction, so that a flag set earlier,
+    /// for example with `set_channel_full_off()`, cannot hide the new value.
+    /// The value must be in the range `[0-4095]`.
+    ///
+    /// Note that a value of 0 results in equal counters, for which the
+    /// datasheet does not specify the output. Use `set_channel_simple()` to
+    /// turn the channel fully off or on instead.
+    pub fn set_channel_value(&mut self, channel: Channel, value: u16) -> Result<(), Error<E>> {
+        self.set_channel_on_off(channel, 0, value)
+    }
+
+    /// Get the number of distinct counter values of a channel.
+    ///
+    /// This is 4096 (`2^RESOLUTION_BITS`), the counter values 0 to `MAX_DUTY`.
+    /// Additionally, a channel can be set always on with the `full ON` flag.
+    pub fn duty_levels(&self) -> u16 {
+        1 << RESOLUTION_BITS
+    }
+
+    /// Set the pulse length of the selected channel in ticks.
+    ///
+    /// The pulse length must be in the range `[0-4096]`. The pulse starts at
+    /// the `ON` offset of the channel (0 by default, see
+    /// `set_channel_on_offset()`). A pulse length of 0 sets the `full OFF`
+    /// flag and 4096 sets the `full ON` flag. Otherwise both flags are cleared.
+    ///
+    /// The `ON` and `OFF` counters are written in a single transaction.
+    /// `Channel::All` behaves as in `set_channel_duty_cycle()`.
+    pub fn set_channel_simple(&mut self, channel: Channel, counts: u16) -> Result<(), Error<E>> {
+        if counts > 4096 {
+            return Err(Error::InvalidInputData);
+        }
+        self.write_channel_pulse(channel, counts)
+    }
+
+    /// Set the selected channel to its maximum output.
+    ///
+    /// With `use_full_flag` the `full ON` flag is set, so the output is
+    /// permanently high. Otherwise the longest regular pulse of 4095 ticks is
+    /// written, with both flags cleared. The output is then low for one tick
+    /// of every period, which is almost but not exactly always on. Some
+    /// loads, like bootstrapped gate drivers, need this edge every period.
+    ///
+    /// Both variants are written with `set_channel_simple()`, so the pulse
+    /// starts at the `ON` offset of the channel.
+    pub fn set_channel_max_on(
+        &mut self,
+        channel: Channel,
+        use_full_flag: bool,
+    ) -> Result<(), Error<E>> {
+        let counts = if use_full_flag { 4096 } else { 4095 };
+        self.set_channel_simple(channel, counts)
+    }
+
+    /// Set the duty cycle of the selected channel.
+    ///
+    /// The duty cycle must be in the range `[0.0-1.0]` and is rounded to the
+    /// nearest of the 4097 possible pulse lengths. The pulse starts at the
+    /// `ON` offset of the channel (0 by default, see `set_channel_on_offset()`).
+    /// A duty cycle of `0.0` sets the `full OFF` flag and `1.0` sets the
+    /// `full ON` flag. Otherwise both flags are cleared.
+    ///
+    /// The `ON` and `OFF` counters are written in a single transaction.
+    ///
+    /// Selecting `Channel::All` writes the `ALL_LED` registers once so that
+    /// every output gets the same pulse. The pulse then starts at the `ON`
+    /// offset shared by all channels (see `set_channel_on_offset(Channel::All, ..)`)
+    /// or at 0 if the channels have different offsets.
+    pub fn set_channel_duty_cycle(&mut self, channel: Channel, duty: f32) -> Result<(), Error<E>> {
+        if duty.is_nan() || duty < 0.0 || duty > 1.0 {
+            return Err(Error::InvalidInputData);
+        }
+        // `round()` is not available in `core`. The value is always positive here.
+        let counts = (duty * 4096.0 + 0.5) as u16;
+        self.write_channel_pulse(channel, counts)
+    }
+
+    /// Set the duty cycle of the selected channel from an 8-bit PWM value.
+    ///
+    /// This follows the common 8-bit PWM convention (like `analogWrite()`):
+    /// 0 sets the `full OFF` flag, 255 sets the `full ON` flag and the
+    /// values in between are mapped to a pulse length of `value * 4095 / 255`
+    /// ticks, starting at the `ON` offset of the channel. The computation is
+    /// done with integers only.
+    ///
+    /// `Channel::All` behaves as in `set_channel_duty_cycle()`.
+    pub fn set_channel_pwm8(&mut self, channel: Channel, value: u8) -> Result<(), Error<E>> {
+        let counts = match value {
+            255 => 4096,
+            v => (u32::from(v) * 4095 / 255) as u16,
+        };
+        self.write_channel_pulse(channel, counts)
+    }
+
+    /// Set the duty cycles of several channels.
+    ///
+    /// `values` yields `(channel, duty)` pairs in any order. The duty cycles
+    /// are validated and converted as in `set_channel_duty_cycle()`. The
+    /// channels are written in ascending order and each run of consecutive
+    /// channels is written in a single transaction with
+    /// `write_channels_from()`. If a channel appears several times, the last
+    /// duty cycle given for it is used.
+    ///
+    /// Returns `Error::InvalidInputData` without writing anything if any
+    /// duty cycle is invalid or if any channel is `Channel::All`.
+    pub fn set_channels_duty<I>(&mut self, values: I) -> Result<(), Error<E>>
+    where
+        I: IntoIterator<Item = (Channel, f32)>,
+    {
+        let mut counts = [None; CHANNEL_COUNT];
+        for (channel, duty) in values {
+            if channel == Channel::All || duty.is_nan() || duty < 0.0 || duty > 1.0 {
+                return Err(Error::InvalidInputData);
+            }
+            // `round()` is not available in `core`. The value is always positive here.
+            let index = channel as usize;
+            counts[index] = Some(snap_min_on(
+                (duty * 4096.0 + 0.5) as u16,
+                self.min_on[index],
+            ));
+        }
+        let mut pulses = [(0, 0); CHANNEL_COUNT];
+        let mut start = 0;
+        while start < CHANNEL_COUNT {
+            if counts[start].is_none() {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end < CHANNEL_COUNT {
+                match counts[end] {
+                    Some(c) => pulses[end] = pulse_registers(self.on_offsets[end], c),
+                    None => break,
+                }
+                end += 1;
+            }
+            let channel = Channel::try_from(start).unwrap_or(Channel::All);
+            self.write_channels_from(channel, &pulses[start..end])?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Set complementary duty cycles on two channels for differential drive.
+    ///
+    /// `ch_a` gets the duty cycle `value` and `ch_b` gets `1.0 - value`, as
+    /// expected by motor drivers controlled by two PWM lines. For example,
+    /// `0.5` drives both lines equally and `1.0` sets `ch_a` fully on and
+    /// `ch_b` fully off. The duty cycles are converted as in
+    /// `set_channel_duty_cycle()` and written with `set_channels_duty()`, so
+    /// both channels are written in a single transaction if they are
+    /// consecutive.
+    ///
+    /// Returns `Error::InvalidInputData` without writing anything if `value`
+    /// is not in the range `[0.0-1.0]`, if both channels are the same or if
+    /// any of them is `Channel::All`.
+    pub fn set_channels_differential(
+        &mut self,
+        ch_a: Channel,
+        ch_b: Channel,
+        value: f32,
+    ) -> Result<(), Error<E>> {
+        if ch_a == ch_b {
+            return Err(Error::InvalidInputData);
+        }
+        self.set_channels_duty([(ch_a, value), (ch_b, 1.0 - value)].iter().cloned())
+    }
+
+    /// Set the duty cycle of the selected channel with additional resolution
+    /// by dithering over consecutive frames.
+    ///
+    /// A duty cycle between two counter values is approximated by alternating
+    /// between both values so that the average over 16 frames is within 1/16
+    /// of a tick of the requested value. This increases the perceived
+    /// resolution of dim LEDs, for example.
+    ///
+    /// `frame` must be incremented by one for every call and the method
+    /// should be called once per PWM period, for example from a timer. Which
+    /// value is written only depends on `frame % 16` so the counter can wrap
+    /// around freely. Apart from that, this behaves like
+    /// `set_channel_duty_cycle()`.
+    pub fn set_channel_duty_dithered(
+        &mut self,
+        channel: Channel,
+        duty: f32,
+        frame: u32,
+    ) -> Result<(), Error<E>> {
+        const FRAMES: u32 = 16;
+        if duty.is_nan() || duty < 0.0 || duty > 1.0 {
+            return Err(Error::InvalidInputData);
+        }
+        let exact = duty * 4096.0;
+        let base = exact as u16;
+        // Number of frames out of `FRAMES` which use the next counter value.
+        // `round()` is not available in `core`. The value is always positive here.
+        let longer = ((exact - f32::from(base)) * FRAMES as f32 + 0.5) as u32;
+        // Spread these frames evenly over the cycle.
+        let n = frame % FRAMES;
+        let counts = if (n + 1) * longer / FRAMES > n * longer / FRAMES {
+            base + 1
+        } else {
+            base
+        };
+        self.write_channel_pulse(channel, counts)
+    }
+
+    /// Set the positions of the rising and falling edges of the selected channel
+    /// as fractions of the period.
+    ///
+    /// Both fractions must be in the range `[0.0-1.0)` and are rounded to the
+    /// nearest counter value. If `off_frac` is smaller than `on_frac`, the pulse
+    /// wraps around the end of the period. For example, `(0.75, 0.25)` gives a
+    /// 50% duty cycle which is high during the last and the first quarter.
+    ///
+    /// Both `full ON` and `full OFF` flags are cleared and the `ON` and `OFF`
+    /// counters are written in a single transaction. Returns
+    /// `Error::InvalidInputData` if both edges end up at the same counter
+    /// value, since the datasheet does not allow that.
+    pub fn set_channel_window(
+        &mut self,
+        channel: Channel,
+        on_frac: f32,
+        off_frac: f32,
+    ) -> Result<(), Error<E>> {
+        let on = fraction_to_counter(on_frac).ok_or(Error::InvalidInputData)?;
+        let off = fraction_to_counter(off_frac).ok_or(Error::InvalidInputData)?;
+        if on == off {
+            return Err(Error::InvalidInputData);
+        }
+        let reg = self.register_on(channel);
+        self.write_two_double_registers(reg, on, off)
+    }
+
+    /// Set the minimum pulse length enforced by the duty cycle setters for the
+    /// selected channel.
+    ///
+    /// Pulses shorter than `counts` ticks, but not 0, are snapped to the
+    /// nearest of 0 and `counts`: up to `counts` if they are at least half as
+    /// long and down to 0, which sets the `full OFF` flag, otherwise. This
+    /// keeps external drivers like MOSFET gates out of their linear region.
+    /// It applies to the setters based on a pulse length, like
+    /// `set_channel_duty_cycle()`, `set_channel_simple()` and
+    /// `set_channel_pulse_us()`, but not to the ones writing the counters
+    /// directly, like `set_channel_on_off()`. `set_channels_budgeted()`
+    /// snaps short pulses down to 0 only, so that it does not exceed its budget.
+    /// Selecting `Channel::All` sets the minimum of every channel.
+    ///
+    /// This does not write anything to the device. A minimum of 0, the
+    /// default, disables the snapping. Returns `Error::InvalidInputData`
+    /// if `counts` is greater than 4095.
+    pub fn set_channel_min_on(&mut self, channel: Channel, counts: u16) -> Result<(), Error<E>> {
+        if counts > 4095 {
+            return Err(Error::InvalidInputData);
+        }
+        if channel == Channel::All {
+            self.min_on = [counts; CHANNEL_COUNT];
+        } else {
+            self.min_on[channel as usize] = counts;
+        }
+        Ok(())
+    }
+
+    /// Set the `ON` counter and the pulse width of the selected channel.
+    ///
+    /// The `OFF` counter is computed as `(on + width) % 4096`, so the pulse
+    /// wraps around the end of the period if needed. Both values must be in
+    /// the range `[0-4095]`. This is the inverse of `get_effective_pulse()`.
+    ///
+    /// This behaves like `set_channel_on_off()` otherwise. In particular a
+    /// width of 0 results in equal counters.
+    pub fn set_channel_on_and_width(
+        &mut self,
+        channel: Channel,
+        on: u16,
+        width: u16,
+    ) -> Result<(), Error<E>> {
+        if on > 4095 || width > 4095 {
+            return Err(Error::InvalidInputData);
+        }
+        self.set_channel_on_off(channel, on, (on + width) % 4096)
+    }
+
+    /// Set the `ON` offset applied by the duty cycle setters for the selected channel.
+    ///
+    /// The pulse then starts at `offset` instead of 0 and the `OFF` counter
+    /// wraps around at the end of the period. This can be used to compensate
+    /// a known propagation delay or to stagger the edges of different channels.
+    /// Selecting `Channel::All` sets the offset of every channel.
+    ///
+    /// This does not write anything to the device. The offset is applied
+    /// on the next duty cycle update.
+    pub fn set_channel_on_offset(&mut self, channel: Channel, offset: u16) -> Result<(), Error<E>> {
+        if offset > 4095 {
+            return Err(Error::InvalidInputData);
+        }
+        if channel == Channel::All {
+            self.on_offsets = [offset; CHANNEL_COUNT];
+        } else {
+            self.on_offsets[channel as usize] = offset;
+        }
+        Ok(())
+    }
+
+    /// Shift the rising edge of every channel to the same position in the
+    /// period while keeping the duty cycles.
+    ///
+    /// `phase` is the position of the rising edges as a fraction of the
+    /// period in the range `[0.0-1.0)`, rounded to the nearest counter value.
+    /// The registers of all channels are read and the pulse length of each
+    /// channel is written back starting at the new position, wrapping around
+    /// the end of the period if necessary. Channels which are always on or off
+    /// stay so.
+    ///
+    /// The shared offset is also stored as the `ON` offset of every channel,
+    /// as if set with `set_channel_on_offset()`, so that later duty cycle
+    /// updates keep the phase.
+    ///
+    /// This is done in a single read and a single write transaction.
+    /// Returns `Error::InvalidInputData` if `phase` is out of range.
+    pub fn set_global_phase(&mut self, phase: f32) -> Result<(), Error<E>> {
+        let offset = fraction_to_counter(phase).ok_or(Error::InvalidInputData)?;
+        let current = self.get_all_channels_on_off_with_flags()?;
+        let mut values = [(0, 0); CHANNEL_COUNT];
+        for (value, pair) in values.iter_mut().zip(current.chunks(2)) {
+            let regs = ChannelRegisters::new(pair[0], pair[1]);
+            let counts = if regs.full_off() {
+                0
+            } else if regs.full_on() {
+                4096
+            } else {
+                regs.off().wrapping_sub(regs.on()) & 0x0FFF
+            };
+            *value = pulse_registers(offset, counts);
+        }
+        self.write_channels_from(Channel::C0, &values)?;
+        self.on_offsets = [offset; CHANNEL_COUNT];
+        Ok(())
+    }
+
+    /// Write a pulse of `counts` ticks starting at the `ON` offset of the channel.
+    ///
+    /// The pulse length is snapped to the minimum pulse length of the channel.
+    pub(crate) fn write_channel_pulse(
+        &mut self,
+        channel: Channel,
+        counts: u16,
+    ) -> Result<(), Error<E>> {
+        let (offset, min_on) = match channel {
+            Channel::All => (
+                shared_or_zero(&self.on_offsets),
+                shared_or_zero(&self.min_on),
+            ),
+            _ => (
+                self.on_offsets[channel as usize],
+                self.min_on[channel as usize],
+            ),
+        };
+        let (on, off) = pulse_registers(offset, snap_min_on(counts, min_on));
+        let reg = self.register_on(channel);
+        self.write_two_double_registers(reg, on, off)
+    }
+
+    /// Set the `ON` and `OFF` counter for each channel at once.
+    ///
+    /// The index of the value in the arrays corresponds to the channel: 0-15.
+    /// This clears all `full ON` and `full OFF` flags.
+    /// Note that the full off setting takes precedence over the `on` settings.
+    /// See section 7.3.3 "LED output and PWM control" of the datasheet for
+    /// further details.
+    ///
+    /// Returns `Error::InvalidInputData` if any value is greater than 4095.
+    pub fn set_all_on_off(&mut self, on: &[u16; 16], off: &[u16; 16]) -> Result<(), Error<E>> {
+        let mut data = [0; CHANNEL_BLOCK_LEN + 1];
+        data[0] = Register::C0_ON_L;
+        for (logical, (on, off)) in on.iter().zip(off).enumerate() {
+            if *on > 4095 || *off > 4095 {
+                return Err(Error::InvalidInputData);
+            }
+            let i = self.physical_index(logical);
+            data[i * CHANNEL_REGISTERS + 1] = *on as u8;
+            data[i * CHANNEL_REGISTERS + 2] = (*on >> 8) as u8;
+            data[i * CHANNEL_REGISTERS + 3] = *off as u8;
+            data[i * CHANNEL_REGISTERS + 4] = (*off >> 8) as u8;
+        }
+        self.enable_auto_increment()?;
+        self.write_registers(&data)
+    }
+
+    /// Set the `ON` and `OFF` values of all channels at once, including
+    /// the `full ON/OFF` flags.
+    ///
+    /// This is the counterpart of `get_all_channels_on_off_with_flags()`: the
+    /// values are given in register order `[C0 ON, C0 OFF, C1 ON, ...]` and
+    /// bits 11:0 of each value are written to the counter and bit 12 to the
+    /// `full ON/OFF` flag. All channels are written in a single transaction
+    /// with `write_channels_from()`.
+    ///
+    /// Returns `Error::InvalidInputData` without writing anything if any
+    /// value has a bit above bit 12 set. `push_frame()` is an alternative
+    /// which ignores these bits instead.
+    ///
+    /// The number of values is checked at compile time. Callers holding a
+    /// slice of dynamic length can convert it with `TryFrom` and handle the
+    /// wrong length themselves:
+    ///
+    /// ```no_run
+    /// use core::convert::TryFrom;
+    /// use linux_embedded_hal::I2cdev;
+    /// use pwm_pca9685::{Address, Error, Pca9685};
+    ///
+    /// let dev = I2cdev::new("/dev/i2c-1").unwrap();
+    /// let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+    /// let received: &[u16] = &[0; 32];
+    /// let values = <&[u16; 32]>::try_from(received).map_err(|_| Error::InvalidInputData)?;
+    /// pwm.set_all_channels_on_off_with_flags(values)?;
+    /// # Ok::<(), Error<linux_embedded_hal::i2cdev::linux::LinuxI2CError>>(())
+    /// ```
+    pub fn set_all_channels_on_off_with_flags(
+        &mut self,
+        values: &[u16; 32],
+    ) -> Result<(), Error<E>> {
+        let mut pairs = [(0, 0); CHANNEL_COUNT];
+        for (pair, value) in pairs.iter_mut().zip(values.chunks(2)) {
+            *pair = (value[0], value[1]);
+        }
+        self.write_channels_from(Channel::C0, &pairs)
+    }
+
+    /// Apply a scene of `ON` and `OFF` values stored, for example, as a constant.
+    ///
+    /// This is the same as `set_all_channels_on_off_with_flags()`. See
+    /// `apply_scene_with_flags()` to override the flags of some channels.
+    pub fn apply_scene(&mut self, scene: &[u16; 32]) -> Result<(), Error<E>> {
+        self.set_all_channels_on_off_with_flags(scene)
+    }
+
+    /// Apply a scene of `ON` and `OFF` values overriding the `full ON/OFF`
+    /// flags of some channels.
+    ///
+    /// `scene` contains the values in register order `[C0 ON, C0 OFF, C1 ON, ...]`
+    /// as in `set_all_channels_on_off_with_flags()` and `flags[n]` overrides
+    /// the flags of channel `n`. The counters are kept in both cases, so
+    /// clearing the flag later with `set_channel_full_on()` or
+    /// `set_channel_full_off()` reveals the pulse of the scene.
+    ///
+    /// All channels are written in a single transaction. Returns
+    /// `Error::InvalidInputData` without writing anything if any value has a
+    /// bit above bit 12 set.
+    ///
+    /// ```no_run
+    /// use linux_embedded_hal::I2cdev;
+    /// use pwm_pca9685::{Address, FlagState, Pca9685};
+    ///
+    /// const SCENE: [u16; 32] = [
+    ///     0, 1024, 0, 1024, 0, 1024, 0, 1024, 0, 2048, 0, 2048, 0, 2048, 0, 2048,
+    ///     0, 3072, 0, 3072, 0, 3072, 0, 3072, 0, 4095, 0, 4095, 0, 4095, 0, 4095,
+    /// ];
+    ///
+    /// let dev = I2cdev::new("/dev/i2c-1").unwrap();
+    /// let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+    /// let mut flags = [FlagState::Keep; 16];
+    /// flags[0] = FlagState::FullOn;
+    /// flags[15] = FlagState::FullOff;
+    /// pwm.apply_scene_with_flags(&SCENE, &flags).unwrap();
+    /// ```
+    pub fn apply_scene_with_flags(
+        &mut self,
+        scene: &[u16; 32],
+        flags: &[FlagState; 16],
+    ) -> Result<(), Error<E>> {
+        let mut pairs = [(0, 0); CHANNEL_COUNT];
+        for ((pair, value), flag) in pairs.iter_mut().zip(scene.chunks(2)).zip(flags) {
+            *pair = match flag {
+                FlagState::Keep => (value[0], value[1]),
+                FlagState::FullOn => (value[0] | 0x1000, value[1] & !0x1000),
+                FlagState::FullOff => (value[0], value[1] | 0x1000),
+            };
+        }
+        self.write_channels_from(Channel::C0, &pairs)
+    }
+
+    /// Set the `ON` and `OFF` counters of consecutive channels.
+    ///
+    /// Writing starts at channel `start` and continues for as many channels
+    /// as there are `(on, off)` pairs in `values`. Each value contains the
+    /// 12-bit counter plus the `full ON/OFF` flag in bit 12, like the values
+    /// returned by `read_channels_into()`. So a value of 4095 or less clears
+    /// the flag.
+    ///
+    /// This is done in a single auto-incremented write, so it is the way to
+    /// update a partial range of channels. If a channel mapping is set with
+    /// `set_channel_mapping()`, the channels are written one by one instead,
+    /// since the range is not necessarily contiguous on the device.
+    ///
+    /// If the write fails, only some of the channels may have been updated.
+    /// See `Error::I2C`.
+    ///
+    /// Returns `Error::InvalidInputData` if `start` is `Channel::All`, if the
+    /// range exceeds the last channel or if any value has a bit above bit 12 set.
+    pub fn write_channels_from(
+        &mut self,
+        start: Channel,
+        values: &[(u16, u16)],
+    ) -> Result<(), Error<E>> {
+        if start == Channel::All
+            || start as usize + values.len() > CHANNEL_COUNT
+            || values.iter().any(|(on, off)| *on > 0x1FFF || *off > 0x1FFF)
+        {
+            return Err(Error::InvalidInputData);
+        }
+        if values.is_empty() {
+            return Ok(());
+        }
+        if self.channel_remap.is_some() {
+            for (i, (on, off)) in values.iter().enumerate() {
+                let channel = Channel::try_from(start as usize + i).unwrap_or(Channel::All);
+                let reg = self.register_on(channel);
+                self.write_two_double_registers(reg, *on, *off)?;
+            }
+            return Ok(());
+        }
+        let mut data = [0; CHANNEL_BLOCK_LEN + 1];
+        data[0] = get_register_on(start);
+        for (bytes, &(on, off)) in data[1..].chunks_mut(4).zip(values) {
+            bytes[0] = on as u8;
+            bytes[1] = (on >> 8) as u8;
+            bytes[2] = off as u8;
+            bytes[3] = (off >> 8) as u8;
+        }
+        self.enable_auto_increment()?;
+        self.write_registers(&data[..values.len() * CHANNEL_REGISTERS + 1])
+    }
+
+    /// Set the `OFF` counter of the channels `start` to `end` (inclusive) to
+    /// the same value and their `ON` counters to 0.
+    ///
+    /// This is done in a single auto-incremented write through
+    /// `write_channels_from()`.
+    ///
+    /// Returns `Error::InvalidInputData` if `start` or `end` is `Channel::All`,
+    /// if `end` is lower than `start` or if `off` is greater than 4095.
+    pub fn set_channel_off_range(
+        &mut self,
+        start: Channel,
+        end: Channel,
+        off: u16,
+    ) -> Result<(), Error<E>> {
+        if start == Channel::All || end == Channel::All || (end as u8) < (start as u8) || off > 4095
+        {
+            return Err(Error::InvalidInputData);
+        }
+        let values = [(0, off); CHANNEL_COUNT];
+        self.write_channels_from(start, &values[..=end as usize - start as usize])
+    }
+
+    /// Set the `ON` counter of every channel to 0.
+    ///
+    /// The `OFF` counters and `full OFF` flags are preserved, so afterwards
+    /// the duty cycle of each channel only depends on its `OFF` counter.
+    /// The `full ON` flags are cleared.
+    ///
+    /// This is done in a single write of the `ALL_LED_ON` registers.
+    pub fn zero_all_on(&mut self) -> Result<(), Error<E>> {
+        self.write_double_register(Register::ALL_C_ON_L, 0)
+    }
+
+    /// Set or clear the `full ON` and `full OFF` flags of every channel at once.
+    ///
+    /// This is done in a single write of the `ALL_LED` registers, which is
+    /// faster than changing the flags channel by channel. The counters of all
+    /// channels are set to 0, so clearing both flags turns all outputs off
+    /// until new pulses are set.
+    ///
+    /// The `full OFF` flag takes precedence over the `full ON` flag, so
+    /// setting both would be the same as setting only `full OFF`. To avoid
+    /// mistakes, this returns `Error::InvalidInputData` if both `full_on`
+    /// and `full_off` are `true`.
+    ///
+    /// See section 7.3.3 "LED output and PWM control" of the datasheet for
+    /// further details.
+    pub fn set_all_channels_flags(
+        &mut self,
+        full_on: bool,
+        full_off: bool,
+    ) -> Result<(), Error<E>> {
+        if full_on && full_off {
+            return Err(Error::InvalidInputData);
+        }
+        let flag = |set: bool| if set { 0x1000 } else { 0 };
+        self.write_two_double_registers(Register::ALL_C_ON_L, flag(full_on), flag(full_off))
     }
 
     /// Set `full ON/OFF` flag on specific register
@@ -39,9 +743,9 @@ where
         let register = register + 1; // flag is in high register
         let reg_h = self.read_register(register)?;
         if flag_value && (reg_h & 0x10 == 0) {
-            self.i2c.write(self.address, &[register, reg_h | 0x10]).map_err(Error::I2C)
+            self.write_registers(&[register, reg_h | 0x10])
         } else if !flag_value && (reg_h & 0x10 != 0) {
-            self.i2c.write(self.address, &[register, reg_h & 0x0f]).map_err(Error::I2C)
+            self.write_registers(&[register, reg_h & 0x0f])
         } else {
             Ok(())
         }
@@ -50,11 +754,13 @@ where
     /// Set the channel always on by setting `full ON` flag.
     ///
     /// Note that the full off setting takes precedence over the `on` settings.
+    /// The output of an always-on channel never goes low, unlike a pulse of
+    /// 4095 ticks. See `set_channel_max_on()`.
     ///
     /// See section 7.3.3 "LED output and PWM control" of the datasheet for
     /// further details.
     pub fn set_channel_full_on(&mut self, channel: Channel, flag_value: bool) -> Result<(), Error<E>> {
-        let reg = get_register_on(channel);
+        let reg = self.register_on(channel);
         self.set_register_full_flag(reg, flag_value)
     }
 
@@ -65,97 +771,596 @@ where
     /// See section 7.3.3 "LED output and PWM control" of the datasheet for
     /// further details.
     pub fn set_channel_full_off(&mut self, channel: Channel, flag_value: bool) -> Result<(), Error<E>> {
-        let reg = get_register_off(channel);
+        let reg = self.register_off(channel);
         self.set_register_full_flag(reg, flag_value)
     }
 
+    /// Mute the selected channel by setting its `full OFF` flag.
+    ///
+    /// The `ON` and `OFF` registers of the channel are read first and kept
+    /// in the driver so that `unmute_channel()` can restore them exactly.
+    /// Muting a channel which is already muted does nothing.
+    ///
+    /// Note that writing a muted channel with other methods overrides the
+    /// mute, but `unmute_channel()` still restores the values from before
+    /// muting it.
+    ///
+    /// Returns `Error::InvalidInputData` for `Channel::All`.
+    pub fn mute_channel(&mut self, channel: Channel) -> Result<(), Error<E>> {
+        if channel == Channel::All {
+            return Err(Error::InvalidInputData);
+        }
+        if self.muted[channel as usize].is_some() {
+            return Ok(());
+        }
+        let (on, off) = self.get_channel_on_off_with_flags(channel)?;
+        let reg = self.register_off(channel) + 1; // flag is in high register
+        self.write_registers(&[reg, (off >> 8) as u8 | 0x10])?;
+        self.muted[channel as usize] = Some((on, off));
+        Ok(())
+    }
+
+    /// Restore the `ON` and `OFF` registers of a channel muted with `mute_channel()`.
+    ///
+    /// Unmuting a channel which is not muted does nothing.
+    ///
+    /// Returns `Error::InvalidInputData` for `Channel::All`.
+    pub fn unmute_channel(&mut self, channel: Channel) -> Result<(), Error<E>> {
+        if channel == Channel::All {
+            return Err(Error::InvalidInputData);
+        }
+        if let Some(values) = self.muted[channel as usize] {
+            self.write_channels_from(channel, &[values])?;
+            self.muted[channel as usize] = None;
+        }
+        Ok(())
+    }
+
+    /// Whether the selected channel is muted with `mute_channel()`.
+    ///
+    /// This does not communicate with the device. `Channel::All` is
+    /// considered muted if every channel is muted.
+    pub fn is_channel_muted(&self, channel: Channel) -> bool {
+        match channel {
+            Channel::All => self.muted.iter().all(Option::is_some),
+            _ => self.muted[channel as usize].is_some(),
+        }
+    }
+
+    /// Light every output in sequence so that an operator can check them.
+    ///
+    /// All channels are switched off first. Then, for each channel in
+    /// order, the output is set always on for `dwell_ms` milliseconds and
+    /// then always off again. Afterwards, the previous `ON` and `OFF`
+    /// registers of all channels are restored.
+    ///
+    /// If an error occurs, the previous state is restored as far as possible
+    /// and the first error is returned.
+    pub fn self_test<D: DelayMs<u16>>(
+        &mut self,
+        delay: &mut D,
+        dwell_ms: u16,
+    ) -> Result<(), Error<E>> {
+        let snapshot = self.get_all_channels_on_off_with_flags()?;
+        let result = self.light_channels_in_sequence(delay, dwell_ms);
+        let mut values = [(0, 0); CHANNEL_COUNT];
+        for (value, pair) in values.iter_mut().zip(snapshot.chunks(2)) {
+            *value = (pair[0], pair[1]);
+        }
+        let restored = self.write_channels_from(Channel::C0, &values);
+        result.and(restored)
+    }
+
+    fn light_channels_in_sequence<D: DelayMs<u16>>(
+        &mut self,
+        delay: &mut D,
+        dwell_ms: u16,
+    ) -> Result<(), Error<E>> {
+        self.set_channel_simple(Channel::All, 0)?;
+        for i in 0..16_u8 {
+            let channel = Channel::try_from(i).unwrap_or(Channel::All);
+            self.set_channel_simple(channel, 4096)?;
+            delay.delay_ms(dwell_ms);
+            self.set_channel_simple(channel, 0)?;
+        }
+        Ok(())
+    }
+
     /// Get the effective pulse length from `OFF` and `ON` counters.
     ///
     /// This takes into account `full ON/OFF` flags.
     pub fn get_effective_pulse(&mut self, channel: Channel) -> Result<u16, Error<E>> {
-        let reg = get_register_on(channel);
-        self.enable_auto_increment()?;
+        let reg = self.register_on(channel);
 
         let mut data = [0, 0, 0, 0];
-        self.i2c.write_read(self.address, &[reg], &mut data).map_err(Error::I2C)?;
+        self.read_registers(reg, &mut data)?;
+        Ok(effective_pulse(ChannelRegisters::from_bytes(data)))
+    }
 
-        // full off - highest priority
-        if (data[3] & 0x10) != 0 {
-            return Ok(0);
-        }
+    /// Get the duty cycle of the selected channel as Q12 fixed-point value.
+    ///
+    /// In the Q12 format the value `x` stands for the duty cycle `x / 4096`,
+    /// so the result in the range `[0-4095]` is the pulse length in ticks as
+    /// returned by `get_effective_pulse()`. A channel which is always on
+    /// returns 4095.
+    pub fn get_channel_duty_q12(&mut self, channel: Channel) -> Result<u16, Error<E>> {
+        self.get_effective_pulse(channel)
+    }
+
+    /// Get the duty cycle of the selected channel in permille.
+    ///
+    /// The result in the range `[0-1000]` is computed with integers only
+    /// from the pulse length returned by `get_effective_pulse()` and rounded
+    /// to the nearest value. A channel which is always on returns 1000.
+    pub fn get_channel_permille(&mut self, channel: Channel) -> Result<u16, Error<E>> {
+        let pulse = u32::from(self.get_effective_pulse(channel)?);
+        Ok(((pulse * 1000 + 2048) / 4096) as u16)
+    }
 
-        // full on
-        if (data[1] & 0x10) != 0 {
-            return Ok(4095);
+    /// Get the duty cycle of the selected channel.
+    ///
+    /// The duty cycle in the range `[0.0-1.0]` is decoded with
+    /// `pulse_from_raw()` as in `get_all_duty_cycles()`, so a channel which
+    /// is always on returns `1.0`. This is the fraction of the period during
+    /// which the PWM signal is active, independently of the output logic
+    /// state. See `get_channel_duty_cycle_logical()`.
+    ///
+    /// Returns `Error::InvalidInputData` for `Channel::All`, since the
+    /// `ALL_LED` registers cannot be read back.
+    pub fn get_channel_duty_cycle(&mut self, channel: Channel) -> Result<f32, Error<E>> {
+        let (on, off) = self.get_channel_on_off_with_flags(channel)?;
+        Ok(pulse_duty_cycle(pulse_from_raw(on, off)))
+    }
+
+    /// Get the duty cycle of the selected channel as seen on the output pin.
+    ///
+    /// This is the same as `get_channel_duty_cycle()` but returns
+    /// `1.0 - duty` if the output logic state is inverted, so the value
+    /// matches the perceived brightness on boards driving LEDs through
+    /// inverted outputs. The output logic state cached by the driver is
+    /// used, as set with `set_output_logic_state()` or adopted by
+    /// `init_if_needed()`, so no additional transfer is needed.
+    pub fn get_channel_duty_cycle_logical(&mut self, channel: Channel) -> Result<f32, Error<E>> {
+        let duty = self.get_channel_duty_cycle(channel)?;
+        if self.config.is_high(BitFlagMode2::Invrt) {
+            Ok(1.0 - duty)
+        } else {
+            Ok(duty)
         }
+    }
 
-        // else normal mode
-        let on_t = ((data[1] as u16) << 8) | data[0] as u16;
-        let off_t = ((data[3] as u16) << 8) | data[2] as u16;
+    /// Set the duty cycle of the selected channel as Q12 fixed-point value.
+    ///
+    /// In the Q12 format the value `x` stands for the duty cycle `x / 4096`,
+    /// so `duty` in the range `[0-4095]` is the pulse length in ticks. It is
+    /// written with `set_channel_simple()`, so 0 sets the `full OFF` flag.
+    ///
+    /// Returns `Error::InvalidInputData` if `duty` is greater than 4095.
+    pub fn set_channel_duty_q12(&mut self, channel: Channel, duty: u16) -> Result<(), Error<E>> {
+        if duty > 4095 {
+            return Err(Error::InvalidInputData);
+        }
+        self.set_channel_simple(channel, duty)
+    }
 
-        if off_t >= on_t {
-            Ok(off_t - on_t)
+    /// Invert the pulse of the selected channel.
+    ///
+    /// This reads the channel and writes a pulse of `4095 - pulse` ticks
+    /// back with `set_channel_simple()`, where `pulse` is the value
+    /// returned by `get_effective_pulse()`. A channel which is always off
+    /// becomes always on and vice versa.
+    ///
+    /// Returns `Error::InvalidInputData` for `Channel::All`, since the
+    /// `ALL_LED` registers cannot be read back.
+    pub fn invert_channel(&mut self, channel: Channel) -> Result<(), Error<E>> {
+        let regs = self.get_channel_registers(channel)?;
+        let counts = if regs.full_off() {
+            4096
+        } else if regs.full_on() {
+            0
         } else {
-            Ok(4095 - on_t + off_t)
+            4095 - effective_pulse(regs)
+        };
+        self.set_channel_simple(channel, counts)
+    }
+
+    /// Check whether the output of the selected channel is always on.
+    ///
+    /// This is the case if the `full ON` flag is set and the `full OFF` flag,
+    /// which takes precedence, is not.
+    ///
+    /// Returns `Error::InvalidInputData` for `Channel::All`, since the
+    /// `ALL_LED` registers cannot be read back.
+    pub fn is_channel_fully_on(&mut self, channel: Channel) -> Result<bool, Error<E>> {
+        let regs = self.get_channel_registers(channel)?;
+        Ok(regs.full_on() && !regs.full_off())
+    }
+
+    /// Check whether the output of the selected channel is always off.
+    ///
+    /// This is the case if the `full OFF` flag is set.
+    ///
+    /// Returns `Error::InvalidInputData` for `Channel::All`, since the
+    /// `ALL_LED` registers cannot be read back.
+    pub fn is_channel_fully_off(&mut self, channel: Channel) -> Result<bool, Error<E>> {
+        Ok(self.get_channel_registers(channel)?.full_off())
+    }
+
+    /// Read the `ON` and `OFF` registers of the selected channel.
+    ///
+    /// Returns `Error::InvalidInputData` for `Channel::All`, since the
+    /// `ALL_LED` registers cannot be read back.
+    pub fn get_channel_registers(
+        &mut self,
+        channel: Channel,
+    ) -> Result<ChannelRegisters, Error<E>> {
+        let (on, off) = self.get_channel_on_off_with_flags(channel)?;
+        Ok(ChannelRegisters::new(on, off))
+    }
+
+    /// Read the `ON` and `OFF` values of the selected channel decoded into
+    /// counters and flags.
+    ///
+    /// The first value is `ON` with the `full ON` flag and the second one is
+    /// `OFF` with the `full OFF` flag.
+    ///
+    /// Returns `Error::InvalidInputData` for `Channel::All`, since the
+    /// `ALL_LED` registers cannot be read back.
+    pub fn get_channel_raw(
+        &mut self,
+        channel: Channel,
+    ) -> Result<(RawCounter, RawCounter), Error<E>> {
+        let (on, off) = self.get_channel_on_off_with_flags(channel)?;
+        Ok((RawCounter::from_raw(on), RawCounter::from_raw(off)))
+    }
+
+    /// Read the `ON` and `OFF` registers of all channels at once.
+    ///
+    /// The values are returned in register order: `[C0 ON, C0 OFF, C1 ON, ...]`.
+    /// Each value contains the 12-bit counter plus the `full ON/OFF` flag
+    /// in bit 12.
+    ///
+    /// This is done in a single auto-incremented read.
+    pub fn get_all_channels_on_off_with_flags(&mut self) -> Result<[u16; 32], Error<E>> {
+        let mut data = [0; CHANNEL_BLOCK_LEN];
+        self.read_registers(Register::C0_ON_L, &mut data)?;
+        let mut values = [0; 32];
+        for (logical, value) in values.chunks_mut(2).enumerate() {
+            let regs =
+                decode_channel_registers(&data[self.physical_index(logical) * CHANNEL_REGISTERS..]);
+            value[0] = regs.on_with_flag();
+            value[1] = regs.off_with_flag();
         }
+        Ok(values)
     }
 
-}
+    /// Read the 12-bit `ON` counters of all channels at once.
+    ///
+    /// The index of the value corresponds to the channel: 0-15. The
+    /// `full ON` flags are not included, see
+    /// `get_all_channels_on_off_with_flags()`.
+    ///
+    /// This is done in a single auto-incremented read.
+    pub fn get_all_channels_on(&mut self) -> Result<[u16; 16], Error<E>> {
+        let values = self.get_all_channels_on_off_with_flags()?;
+        let mut on = [0; CHANNEL_COUNT];
+        for (counter, pair) in on.iter_mut().zip(values.chunks(2)) {
+            *counter = pair[0] & 0x0FFF;
+        }
+        Ok(on)
+    }
 
-macro_rules! get_register {
-    ($channel:expr, $($C:ident, $reg:ident),*) => {
-        match $channel {
-            $(
-                Channel::$C  => Register::$reg,
-            )*
+    /// Read the 12-bit `OFF` counters of all channels at once.
+    ///
+    /// The index of the value corresponds to the channel: 0-15. The
+    /// `full OFF` flags are not included, see
+    /// `get_all_channels_on_off_with_flags()`.
+    ///
+    /// This is done in a single auto-incremented read.
+    pub fn get_all_channels_off(&mut self) -> Result<[u16; 16], Error<E>> {
+        let values = self.get_all_channels_on_off_with_flags()?;
+        let mut off = [0; CHANNEL_COUNT];
+        for (counter, pair) in off.iter_mut().zip(values.chunks(2)) {
+            *counter = pair[1] & 0x0FFF;
         }
-    };
+        Ok(off)
+    }
+
+    /// Read the duty cycles of all channels at once.
+    ///
+    /// The index of the value corresponds to the channel: 0-15. Each duty
+    /// cycle in the range `[0.0-1.0]` is decoded with `pulse_from_raw()`, so
+    /// a channel which is always on returns `1.0` and a channel which is
+    /// always off returns `0.0`. Otherwise the pulse length is divided by
+    /// 4096, as in `set_channel_duty_cycle()`.
+    ///
+    /// This is done in a single auto-incremented read.
+    pub fn get_all_duty_cycles(&mut self) -> Result<[f32; 16], Error<E>> {
+        let values = self.get_all_channels_on_off_with_flags()?;
+        let mut duty = [0.0; CHANNEL_COUNT];
+        for (duty, pair) in duty.iter_mut().zip(values.chunks(2)) {
+            *duty = pulse_duty_cycle(pulse_from_raw(pair[0], pair[1]));
+        }
+        Ok(duty)
+    }
+
+    /// Count the channels whose rising edges coincide.
+    ///
+    /// This reads all channels in a single auto-incremented read and returns
+    /// the largest number of channels switching on at the same tick. Only
+    /// channels with a pulse as decoded by `pulse_from_raw()` are considered,
+    /// since channels which are always on or always off do not switch. This
+    /// is useful to check how well the pulses are staggered with
+    /// `set_channel_on_offset()`, for example to reduce EMI.
+    pub fn count_simultaneous_edges(&mut self) -> Result<u16, Error<E>> {
+        let values = self.get_all_channels_on_off_with_flags()?;
+        let mut rising = [None; CHANNEL_COUNT];
+        for (edge, pair) in rising.iter_mut().zip(values.chunks(2)) {
+            if let PulseResult::Length(length) = pulse_from_raw(pair[0], pair[1]) {
+                if length > 0 {
+                    *edge = Some(pair[0]);
+                }
+            }
+        }
+        let mut max = 0;
+        for edge in rising.iter().filter(|e| e.is_some()) {
+            let count = rising.iter().filter(|e| *e == edge).count() as u16;
+            max = max.max(count);
+        }
+        Ok(max)
+    }
+
+    /// Read the raw contents of the `ON` and `OFF` registers of all channels.
+    ///
+    /// This returns the bytes of the registers `0x06` to `0x45` exactly as
+    /// they are stored in the device, without assembling them into counters
+    /// or applying the channel mapping. It is meant for debugging, for
+    /// example to compare against a bus capture.
+    ///
+    /// This is done in a single auto-incremented read.
+    pub fn dump_channel_registers(&mut self) -> Result<[u8; 64], Error<E>> {
+        let mut data = [0; CHANNEL_BLOCK_LEN];
+        self.read_registers(Register::C0_ON_L, &mut data)?;
+        Ok(data)
+    }
+
+    /// Read the `ON` and `OFF` registers of consecutive channels into a buffer.
+    ///
+    /// Reading starts at channel `start` and continues for as many channels as
+    /// `out` can hold. Each entry is an `(on, off)` pair containing the 12-bit
+    /// counter plus the `full ON/OFF` flag in bit 12.
+    ///
+    /// This is done in a single auto-incremented read and does not allocate,
+    /// so it is the way to read a partial range of channels.
+    ///
+    /// Returns `Error::InvalidInputData` if `start` is `Channel::All` or
+    /// if the range exceeds the last channel.
+    pub fn read_channels_into(
+        &mut self,
+        start: Channel,
+        out: &mut [(u16, u16)],
+    ) -> Result<(), Error<E>> {
+        if start == Channel::All || start as usize + out.len() > CHANNEL_COUNT {
+            return Err(Error::InvalidInputData);
+        }
+        if out.is_empty() {
+            return Ok(());
+        }
+        if self.channel_remap.is_some() {
+            // The logical range is not necessarily contiguous on the device.
+            let values = self.get_all_channels_on_off_with_flags()?;
+            for (value, pair) in out.iter_mut().zip(values.chunks(2).skip(start as usize)) {
+                *value = (pair[0], pair[1]);
+            }
+            return Ok(());
+        }
+        let mut data = [0; CHANNEL_BLOCK_LEN];
+        let data = &mut data[..out.len() * CHANNEL_REGISTERS];
+        self.read_registers(get_register_on(start), data)?;
+        for (value, bytes) in out.iter_mut().zip(data.chunks(4)) {
+            let regs = decode_channel_registers(bytes);
+            *value = (regs.on_with_flag(), regs.off_with_flag());
+        }
+        Ok(())
+    }
+
+    /// Set the mapping from logical channels to the channels on the device.
+    ///
+    /// Some boards number their outputs differently than the device.
+    /// `map[n]` is the device channel used whenever channel `n` is selected,
+    /// so for a board that numbers its outputs in reverse order the map would
+    /// be `[15, 14, ..., 0]`. This applies to all channel methods, including
+    /// the bulk methods, where index `n` of the values is written to or read
+    /// from device channel `map[n]`. `Channel::All` is not affected.
+    ///
+    /// The default mapping is the identity. Returns `Error::InvalidInputData`
+    /// if the map is not a permutation of the channels 0-15.
+    pub fn set_channel_mapping(&mut self, map: [u8; 16]) -> Result<(), Error<E>> {
+        let mut seen = 0_u16;
+        for &physical in &map {
+            if usize::from(physical) >= CHANNEL_COUNT || seen & (1 << physical) != 0 {
+                return Err(Error::InvalidInputData);
+            }
+            seen |= 1 << physical;
+        }
+        let is_identity = map.iter().enumerate().all(|(i, &p)| i == usize::from(p));
+        self.channel_remap = if is_identity { None } else { Some(map) };
+        Ok(())
+    }
+
+    /// Translate a logical channel index into the channel index on the device.
+    pub(crate) fn physical_index(&self, logical: usize) -> usize {
+        match self.channel_remap {
+            Some(map) => usize::from(map[logical]),
+            None => logical,
+        }
+    }
+
+    /// Translate a logical channel into the channel on the device.
+    fn physical_channel(&self, channel: Channel) -> Channel {
+        if channel == Channel::All {
+            return channel;
+        }
+        Channel::try_from(self.physical_index(channel as usize)).unwrap_or(channel)
+    }
+
+    fn register_on(&self, channel: Channel) -> u8 {
+        get_register_on(self.physical_channel(channel))
+    }
+
+    fn register_off(&self, channel: Channel) -> u8 {
+        get_register_off(self.physical_channel(channel))
+    }
+
+    fn get_channel_on_off_with_flags(&mut self, channel: Channel) -> Result<(u16, u16), Error<E>> {
+        let mut value = [(0, 0)];
+        self.read_channels_into(channel, &mut value)?;
+        Ok(value[0])
+    }
+}
+
+/// Decode the pulse of a channel from its `ON` and `OFF` register values.
+///
+/// `on` and `off` contain the 12-bit counter and the `full ON/OFF` flag in
+/// bit 12, as returned by `read_channels_into()`. The `full OFF`
+/// flag takes precedence over the `full ON` flag, which in turn takes
+/// precedence over the counters. Only if no flag is set is the pulse
+/// length computed from the counters, wrapping around at the end of the
+/// period.
+///
+/// Which helper to use:
+///
+/// | Helper                    | Reads from        | Flags considered | Result                   |
+/// |---------------------------|-------------------|------------------|--------------------------|
+/// | `pulse_from_raw()`        | register values   | yes              | `PulseResult`            |
+/// | `get_effective_pulse()`   | the device        | yes              | ticks, always on is 4095 |
+/// | `get_channel_registers()` | the device        | no               | counters and flags apart |
+/// | `ChannelRegisters::on()`  | register values   | no               | 12-bit counter           |
+///
+/// ```
+/// use pwm_pca9685::{pulse_from_raw, PulseResult};
+///
+/// assert_eq!(PulseResult::Length(1024), pulse_from_raw(1024, 2048));
+/// assert_eq!(PulseResult::Full, pulse_from_raw(0x1000, 2048));
+/// assert_eq!(PulseResult::Off, pulse_from_raw(0x1000, 0x1000));
+/// ```
+pub fn pulse_from_raw(on: u16, off: u16) -> PulseResult {
+    let regs = ChannelRegisters::new(on, off);
+    // full off - highest priority
+    if regs.full_off() {
+        return PulseResult::Off;
+    }
+
+    // full on
+    if regs.full_on() {
+        return PulseResult::Full;
+    }
+
+    // else normal mode
+    let on_t = regs.on();
+    let off_t = regs.off();
+
+    // A pulse wrapping around the end of the period lasts `4096 - on + off` ticks.
+    PulseResult::Length(off_t.wrapping_sub(on_t) & 0x0FFF)
+}
+
+/// Compute the pulse length of a channel taking into account the
+/// `full ON/OFF` flags.
+fn effective_pulse(regs: ChannelRegisters) -> u16 {
+    match pulse_from_raw(regs.on_with_flag(), regs.off_with_flag()) {
+        PulseResult::Off => 0,
+        PulseResult::Full => 4095,
+        PulseResult::Length(ticks) => ticks,
+    }
+}
+
+/// Compute the `ON` and `OFF` register values for a pulse of `counts` ticks
+/// in the range `[0-4096]` starting at `offset`, using the `full ON/OFF` flags
+/// for the extremes. The `OFF` counter wraps around at the end of the period.
+pub(crate) fn pulse_registers(offset: u16, counts: u16) -> (u16, u16) {
+    match counts {
+        0 => (0, 0x1000),
+        c if c >= 4096 => (0x1000, 0),
+        c => (offset, (offset + c) % 4096),
+    }
+}
+
+/// Convert a decoded pulse into a duty cycle in the range `[0.0-1.0]`.
+fn pulse_duty_cycle(pulse: PulseResult) -> f32 {
+    match pulse {
+        PulseResult::Full => 1.0,
+        PulseResult::Off => 0.0,
+        PulseResult::Length(length) => f32::from(length) / 4096.0,
+    }
+}
+
+/// Snap a pulse of `counts` ticks shorter than `min_on`, but not 0, to the
+/// nearest of 0 and `min_on`.
+pub(crate) fn snap_min_on(counts: u16, min_on: u16) -> u16 {
+    match counts {
+        c if c == 0 || c >= min_on => c,
+        c if c * 2 >= min_on => min_on,
+        _ => 0,
+    }
+}
+
+/// Get the value shared by all channels, or 0 if they differ.
+fn shared_or_zero(values: &[u16; CHANNEL_COUNT]) -> u16 {
+    if values.iter().all(|v| *v == values[0]) {
+        values[0]
+    } else {
+        0
+    }
+}
+
+/// Convert a position within the period in the range `[0.0-1.0)` into the
+/// nearest counter value.
+fn fraction_to_counter(fraction: f32) -> Option<u16> {
+    if fraction.is_nan() || fraction < 0.0 || fraction >= 1.0 {
+        return None;
+    }
+    // `round()` is not available in `core`. The value is always positive here.
+    // Rounding up to 4096 is the start of the next period, which is 0.
+    Some((fraction * 4096.0 + 0.5) as u16 % 4096)
+}
+
+/// Decode the first four bytes of `bytes` as the registers of a channel.
+fn decode_channel_registers(bytes: &[u8]) -> ChannelRegisters {
+    ChannelRegisters::from_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl Channel {
+    /// Get the address of the `ON_L` register of this channel.
+    ///
+    /// The `ON_H`, `OFF_L` and `OFF_H` registers follow at the next
+    /// addresses. For `Channel::All` this is the `ALL_LED_ON_L` register.
+    /// This refers to the channels of the device, so any channel mapping
+    /// set with `Pca9685::set_channel_mapping()` is not applied.
+    pub fn on_register(self) -> u8 {
+        get_register_on(self)
+    }
+
+    /// Get the address of the `OFF_L` register of this channel.
+    ///
+    /// The `OFF_H` register follows at the next address. For `Channel::All`
+    /// this is the `ALL_LED_OFF_L` register. This refers to the channels of
+    /// the device, so any channel mapping set with
+    /// `Pca9685::set_channel_mapping()` is not applied.
+    pub fn off_register(self) -> u8 {
+        get_register_off(self)
+    }
 }
 
 fn get_register_on(channel: Channel) -> u8 {
-    get_register!(
-        channel, C0, C0_ON_L, C1, C1_ON_L, C2, C2_ON_L, C3, C3_ON_L, C4, C4_ON_L, C5, C5_ON_L, C6,
-        C6_ON_L, C7, C7_ON_L, C8, C8_ON_L, C9, C9_ON_L, C10, C10_ON_L, C11, C11_ON_L, C12,
-        C12_ON_L, C13, C13_ON_L, C14, C14_ON_L, C15, C15_ON_L, All, ALL_C_ON_L
-    )
+    match channel {
+        Channel::All => Register::ALL_C_ON_L,
+        channel => channel_on_register(channel as usize),
+    }
 }
 
 fn get_register_off(channel: Channel) -> u8 {
-    get_register!(
-        channel,
-        C0,
-        C0_OFF_L,
-        C1,
-        C1_OFF_L,
-        C2,
-        C2_OFF_L,
-        C3,
-        C3_OFF_L,
-        C4,
-        C4_OFF_L,
-        C5,
-        C5_OFF_L,
-        C6,
-        C6_OFF_L,
-        C7,
-        C7_OFF_L,
-        C8,
-        C8_OFF_L,
-        C9,
-        C9_OFF_L,
-        C10,
-        C10_OFF_L,
-        C11,
-        C11_OFF_L,
-        C12,
-        C12_OFF_L,
-        C13,
-        C13_OFF_L,
-        C14,
-        C14_OFF_L,
-        C15,
-        C15_OFF_L,
-        All,
-        ALL_C_OFF_L
-    )
+    match channel {
+        Channel::All => Register::ALL_C_OFF_L,
+        channel => channel_off_register(channel as usize),
+    }
 }
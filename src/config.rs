@@ -1,5 +1,7 @@
 //! Device configuration
 
+use crate::{DisabledOutputValue, OutputConfig, OutputDriver, OutputLogicState, OutputStateChange};
+
 pub enum BitFlag {
     Mode1(BitFlagMode1),
     Mode2(BitFlagMode2),
@@ -78,6 +80,65 @@ impl Config {
             },
         }
     }
+
+    pub fn output_config(self) -> OutputConfig {
+        let driver = if self.is_high(BitFlagMode2::OutDrv) {
+            OutputDriver::TotemPole
+        } else {
+            OutputDriver::OpenDrain
+        };
+        let invert = if self.is_high(BitFlagMode2::Invrt) {
+            OutputLogicState::Inverted
+        } else {
+            OutputLogicState::Direct
+        };
+        let change = if self.is_high(BitFlagMode2::Och) {
+            OutputStateChange::OnAck
+        } else {
+            OutputStateChange::OnStop
+        };
+        // OUTNE = 1x always means high-impedance.
+        let disabled_state = if self.is_high(BitFlagMode2::OutNe1) {
+            DisabledOutputValue::HighImpedance
+        } else if self.is_high(BitFlagMode2::OutNe0) {
+            DisabledOutputValue::OutputDriver
+        } else {
+            DisabledOutputValue::Zero
+        };
+        OutputConfig {
+            driver,
+            invert,
+            change,
+            disabled_state,
+        }
+    }
+
+    pub fn with_output_config(self, output: OutputConfig) -> Self {
+        let config = match output.driver {
+            OutputDriver::TotemPole => self.with_high(BitFlagMode2::OutDrv),
+            OutputDriver::OpenDrain => self.with_low(BitFlagMode2::OutDrv),
+        };
+        let config = match output.invert {
+            OutputLogicState::Direct => config.with_low(BitFlagMode2::Invrt),
+            OutputLogicState::Inverted => config.with_high(BitFlagMode2::Invrt),
+        };
+        let config = match output.change {
+            OutputStateChange::OnStop => config.with_low(BitFlagMode2::Och),
+            OutputStateChange::OnAck => config.with_high(BitFlagMode2::Och),
+        };
+        // Only the OUTNE combinations 00, 01 and 10 are ever written.
+        match output.disabled_state {
+            DisabledOutputValue::Zero => config
+                .with_low(BitFlagMode2::OutNe0)
+                .with_low(BitFlagMode2::OutNe1),
+            DisabledOutputValue::OutputDriver => config
+                .with_high(BitFlagMode2::OutNe0)
+                .with_low(BitFlagMode2::OutNe1),
+            DisabledOutputValue::HighImpedance => config
+                .with_low(BitFlagMode2::OutNe0)
+                .with_high(BitFlagMode2::OutNe1),
+        }
+    }
 }
 
 impl Default for Config {
@@ -116,4 +177,34 @@ mod tests {
     fn config_mode2_is_not_high() {
         assert!(!Config::default().is_high(BitFlagMode2::Invrt));
     }
+
+    #[test]
+    fn default_output_config_is_correct() {
+        assert_eq!(OutputConfig::default(), Config::default().output_config());
+    }
+
+    #[test]
+    fn output_config_round_trips() {
+        let output = OutputConfig {
+            driver: OutputDriver::OpenDrain,
+            invert: OutputLogicState::Inverted,
+            change: OutputStateChange::OnAck,
+            disabled_state: DisabledOutputValue::HighImpedance,
+        };
+        let config = Config::default().with_output_config(output);
+        assert_eq!(0b0001_1010, config.mode2);
+        assert_eq!(output, config.output_config());
+    }
+
+    #[test]
+    fn outne_both_high_is_high_impedance() {
+        let config = Config {
+            mode1: 0,
+            mode2: BitFlagMode2::OutNe1 as u8 | BitFlagMode2::OutNe0 as u8,
+        };
+        assert_eq!(
+            DisabledOutputValue::HighImpedance,
+            config.output_config().disabled_state
+        );
+    }
 }
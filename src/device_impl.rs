@@ -1,8 +1,11 @@
 use crate::{
     config::{BitFlagMode1, BitFlagMode2, Config},
+    frequency::PRESCALE_DEFAULT,
     hal::{blocking::delay::DelayUs, blocking::i2c},
-    Address, DisabledOutputValue, Error, OutputDriver, OutputLogicState, OutputStateChange,
-    Pca9685, ProgrammableAddress, Register,
+    register_access::CHANNEL_COUNT,
+    types::DEVICE_BASE_ADDRESS,
+    Address, AddressingIssue, DisabledOutputValue, Error, OutputConfig, OutputDriver,
+    OutputLogicState, OutputStateChange, Pca9685, ProgrammableAddress, Register,
 };
 
 impl<I2C, E> Pca9685<I2C>
@@ -15,11 +18,94 @@ where
 
         Self::check_address(a.0)?;
 
-        Ok(Pca9685 {
-            i2c,
-            address: a.0,
-            config: Config::default(),
-        })
+        Ok(Self::with_defaults(i2c, a.0))
+    }
+
+    /// Create a new instance of the device running at the PWM frequency `hz`.
+    ///
+    /// This sets the frequency as with `set_frequency()`, enables the
+    /// controller and waits 500us for the oscillator to be stable, so the
+    /// returned device is ready to use, for example at 50 Hz for servos.
+    ///
+    /// Returns `Error::InvalidInputData` without communicating with the
+    /// device if the address is invalid or the frequency is out of the
+    /// achievable range (prescale values 3-255). The I²C bus is returned
+    /// together with any error so that it can be used again.
+    pub fn new_with_frequency<A: Into<Address>>(
+        i2c: I2C,
+        address: A,
+        hz: f32,
+        delay: &mut impl DelayUs<u16>,
+    ) -> Result<Self, (Error<E>, I2C)> {
+        let a = address.into();
+        if let Err(e) = Self::check_address(a.0) {
+            return Err((e, i2c));
+        }
+        let mut pwm = Self::with_defaults(i2c, a.0);
+        if let Err(e) = pwm.set_frequency(hz).and_then(|_| pwm.enable()) {
+            return Err((e, pwm.destroy()));
+        }
+        delay.delay_us(500_u16);
+        Ok(pwm)
+    }
+
+    /// Scan the bus for devices responding in the PCA9685 address range.
+    ///
+    /// This tries to read the `MODE1` register at every valid address in the
+    /// range `0x40-0x7F` and stores the addresses that respond into `found`.
+    /// The LED all-call address `0x70` is skipped. Returns the number of
+    /// addresses found, which is limited by the length of `found`.
+    ///
+    /// *Warning:* This communicates with every address in the range.
+    /// Any other device on the bus using one of these addresses will be
+    /// reported as well and may be confused by the read.
+    pub fn scan_into(i2c: &mut I2C, found: &mut [u8]) -> usize {
+        let mut count = 0;
+        for address in DEVICE_BASE_ADDRESS..=0x7F {
+            if count == found.len() {
+                break;
+            }
+            if Self::check_address(address).is_err() {
+                continue;
+            }
+            let mut data = [0];
+            if i2c
+                .write_read(address, &[Register::MODE1], &mut data)
+                .is_ok()
+            {
+                found[count] = address;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Scan the bus for devices responding in the PCA9685 address range.
+    ///
+    /// See [`scan_into()`](Pca9685::scan_into) for details, including the
+    /// warning about communicating with the whole address range.
+    #[cfg(feature = "heapless")]
+    pub fn scan(i2c: &mut I2C) -> heapless::Vec<u8, 64> {
+        let mut found = [0; 64];
+        let count = Self::scan_into(i2c, &mut found);
+        found[..count].iter().cloned().collect()
+    }
+
+    /// Get whether an I²C bus error occurred.
+    ///
+    /// This returns `true` if any I²C bus error occurred since the driver was
+    /// created or since the last call to `clear_error()`, even if the error
+    /// was already handled by the caller. This allows a supervisory task to
+    /// notice transient bus issues. No error details are kept: the error of
+    /// the bus itself is returned only by the failing method, since it cannot
+    /// necessarily be copied.
+    pub fn bus_error_occurred(&self) -> bool {
+        self.bus_error
+    }
+
+    /// Clear the latched bus error state. See `bus_error_occurred()`.
+    pub fn clear_error(&mut self) {
+        self.bus_error = false;
     }
 
     /// Destroy driver instance, return I²C bus instance.
@@ -39,6 +125,28 @@ where
         self.write_mode1(config.with_high(BitFlagMode1::Sleep))
     }
 
+    /// Prepare the controller for a future restart without changing the
+    /// `SLEEP` state.
+    ///
+    /// This writes the `RESTART` bit, which clears any stale restart state,
+    /// so that the next time the controller is put to sleep with
+    /// `disable()` while PWM channels are active, the device sets the
+    /// `RESTART` bit at the end of the PWM refresh cycle. Only then can the
+    /// channels be restarted with `restart()` or `restart_nonblocking()`.
+    ///
+    /// This only arms the restart logic. It neither puts the controller to
+    /// sleep nor wakes it up. If the controller is already sleeping, the
+    /// PWM channels were already stopped and there is nothing to restart.
+    /// Use `enable_restart_and_disable()` to arm and sleep in one step.
+    pub fn enable_restart(&mut self) -> Result<(), Error<E>> {
+        let previous = self.config;
+        self.write_mode1(previous.with_high(BitFlagMode1::Restart))?;
+        // Do not store restart bit high as writing this bit high again
+        // would internally clear it to 0. Writing 0 has no effect.
+        self.config = previous;
+        Ok(())
+    }
+
     /// Put the controller to sleep while keeping the PWM register
     /// contents in preparation for a future restart.
     pub fn enable_restart_and_disable(&mut self) -> Result<(), Error<E>> {
@@ -94,11 +202,47 @@ where
         Ok(())
     }
 
+    /// Check whether the `RESTART` bit is set in the device.
+    ///
+    /// This reads the `MODE1` register. The bit is set when the device was
+    /// put to sleep while PWM channels were active, for example with
+    /// `enable_restart_and_disable()`, and means that `restart()` is needed
+    /// to resume the outputs.
+    pub fn needs_restart(&mut self) -> Result<bool, Error<E>> {
+        let mode1 = self.read_register(Register::MODE1)?;
+        Ok(mode1 & BitFlagMode1::Restart as u8 != 0)
+    }
+
+    /// Restart the device if needed and re-apply the last frame.
+    ///
+    /// This is meant to be called periodically. If `needs_restart()` reports
+    /// that the `RESTART` bit is set, the device is restarted with
+    /// `restart()` and, if frame retention is enabled with
+    /// `set_frame_retention()`, the last frame given to `push_frame()` is
+    /// written again completely. Otherwise only `MODE1` is read.
+    pub fn service_watchdog(&mut self, delay: &mut impl DelayUs<u16>) -> Result<(), Error<E>> {
+        if !self.needs_restart()? {
+            return Ok(());
+        }
+        self.restart(delay)?;
+        if let Some(frame) = self.retained_frame {
+            self.last_frame = None;
+            self.push_frame(&frame)?;
+        }
+        Ok(())
+    }
+
     /// Set one of the programmable addresses.
     ///
+    /// The address is stored in bits 7:1 of the register as described in
+    /// the datasheet.
+    ///
     /// Initially these are not enabled. Once you set this, you can call
     /// `enable_programmable_address()` and then use `set_address()` to configure
     /// the driver to use the new address.
+    ///
+    /// Returns `Error::AddressConflict` if the address is the one currently
+    /// used by the driver, since the device would then respond to it twice.
     pub fn set_programmable_address<A: Into<Address>>(
         &mut self,
         address_type: ProgrammableAddress,
@@ -107,15 +251,140 @@ where
         let a = address.into();
 
         Self::check_address(a.0)?;
-        let reg = match address_type {
+        if a.0 == self.address {
+            return Err(Error::AddressConflict);
+        }
+        let reg = Self::get_programmable_address_register(address_type);
+        self.write_registers(&[reg, a.0 << 1])
+    }
+
+    /// Read one of the programmable addresses from the device.
+    ///
+    /// The address is decoded from bits 7:1 of the register, as written by
+    /// `set_programmable_address()`. This does not tell whether the address
+    /// is enabled, see `subaddress_config()`.
+    pub fn get_programmable_address(
+        &mut self,
+        address_type: ProgrammableAddress,
+    ) -> Result<u8, Error<E>> {
+        let reg = Self::get_programmable_address_register(address_type);
+        Ok(self.read_register(reg)? >> 1)
+    }
+
+    /// Read the three subaddresses and whether each of them is enabled.
+    ///
+    /// This reads `MODE1` and the subaddress registers in a single
+    /// auto-incremented read, so multi-device setups can verify the
+    /// configuration set with `configure_subaddresses()`, for example.
+    /// The addresses are decoded as in `get_programmable_address()`.
+    pub fn subaddress_config(&mut self) -> Result<[(u8, bool); 3], Error<E>> {
+        const TYPES: [ProgrammableAddress; 3] = [
+            ProgrammableAddress::Subaddress1,
+            ProgrammableAddress::Subaddress2,
+            ProgrammableAddress::Subaddress3,
+        ];
+        let mut data = [0; 5];
+        self.read_registers(Register::MODE1, &mut data)?;
+        let mut config = [(0, false); 3];
+        for ((entry, address_type), reg) in config.iter_mut().zip(&TYPES).zip(&data[2..]) {
+            let flag = Self::get_subaddr_bitflag(*address_type) as u8;
+            *entry = (reg >> 1, data[0] & flag != 0);
+        }
+        Ok(config)
+    }
+
+    /// Set and enable or disable the three subaddresses at once.
+    ///
+    /// Each `Some` address is written to its subaddress register and the
+    /// subaddress is enabled. Subaddresses given as `None` are disabled and
+    /// their registers are not modified. The enable bits of all three are
+    /// updated in a single `MODE1` write.
+    ///
+    /// All addresses are validated as in `set_programmable_address()` before
+    /// anything is written.
+    pub fn configure_subaddresses(&mut self, addrs: [Option<u8>; 3]) -> Result<(), Error<E>> {
+        const TYPES: [ProgrammableAddress; 3] = [
+            ProgrammableAddress::Subaddress1,
+            ProgrammableAddress::Subaddress2,
+            ProgrammableAddress::Subaddress3,
+        ];
+        for address in addrs.iter().flatten() {
+            Self::check_address(*address)?;
+            if *address == self.address {
+                return Err(Error::AddressConflict);
+            }
+        }
+        let mut config = self.config;
+        for (address_type, address) in TYPES.iter().zip(&addrs) {
+            let flag = Self::get_subaddr_bitflag(*address_type);
+            if let Some(address) = address {
+                self.set_programmable_address(*address_type, *address)?;
+                config = config.with_high(flag);
+            } else {
+                config = config.with_low(flag);
+            }
+        }
+        self.write_mode1(config)
+    }
+
+    /// Check the addressing configuration of the device for suspicious settings.
+    ///
+    /// This reads `MODE1` and the programmable address registers and returns
+    /// `Error::Addressing` describing the first issue found:
+    /// - The driver communicates through the enabled LED all call address,
+    ///   so the writes reach every device on the bus and not only this one.
+    /// - An enabled subaddress still has its power-on value, which is the
+    ///   same on every device.
+    /// - An enabled programmable address has a reserved value.
+    /// - Two enabled programmable addresses have the same value.
+    ///
+    /// This is meant as a troubleshooting aid and does not change anything.
+    pub fn check_addressing(&mut self) -> Result<(), Error<E>> {
+        const TYPES: [ProgrammableAddress; 4] = [
+            ProgrammableAddress::Subaddress1,
+            ProgrammableAddress::Subaddress2,
+            ProgrammableAddress::Subaddress3,
+            ProgrammableAddress::AllCall,
+        ];
+        const POWER_ON: [u8; 4] = [0x71, 0x72, 0x74, 0x70];
+        let mut data = [0; 6];
+        self.read_registers(Register::MODE1, &mut data)?;
+        let mode1 = data[0];
+        let mut enabled = [None; 4];
+        for (i, address_type) in TYPES.iter().enumerate() {
+            if mode1 & Self::get_subaddr_bitflag(*address_type) as u8 != 0 {
+                enabled[i] = Some(data[i + 2] >> 1);
+            }
+        }
+        if enabled[3] == Some(self.address) {
+            return Err(Error::Addressing(AddressingIssue::DriverUsesAllCall));
+        }
+        for (i, address) in enabled.iter().enumerate() {
+            let address = match address {
+                Some(address) => *address,
+                None => continue,
+            };
+            let issue = if address == POWER_ON[i] && TYPES[i] != ProgrammableAddress::AllCall {
+                AddressingIssue::Unset(TYPES[i])
+            } else if address != POWER_ON[3] && Self::check_address(address).is_err() {
+                AddressingIssue::Invalid(TYPES[i])
+            } else if let Some(j) = (i + 1..4).find(|j| enabled[*j] == Some(address)) {
+                AddressingIssue::Duplicate(TYPES[i], TYPES[j])
+            } else {
+                continue;
+            };
+            return Err(Error::Addressing(issue));
+        }
+        Ok(())
+    }
+
+    fn get_programmable_address_register(address_type: ProgrammableAddress) -> u8 {
+        match address_type {
             ProgrammableAddress::Subaddress1 => Register::SUBADDR1,
             ProgrammableAddress::Subaddress2 => Register::SUBADDR2,
             ProgrammableAddress::Subaddress3 => Register::SUBADDR3,
             ProgrammableAddress::AllCall => Register::ALL_CALL_ADDR,
-        };
-        self.i2c
-            .write(self.address, &[reg, a.0])
-            .map_err(Error::I2C)
+        }
     }
 
     fn get_subaddr_bitflag(address_type: ProgrammableAddress) -> BitFlagMode1 {
@@ -147,6 +416,38 @@ where
         self.write_mode1(config.with_low(flag))
     }
 
+    /// Program and enable a programmable address, then use it for
+    /// communication.
+    ///
+    /// This does `set_programmable_address()`, `enable_programmable_address()`
+    /// and `set_address()` in the right order: the address used by the driver
+    /// is only changed once the device responds to the new address, so all
+    /// subsequent calls reach the device. If any step fails, the driver keeps
+    /// using the previous address.
+    ///
+    /// The device still responds to the previous address as well.
+    pub fn reprogram_and_retarget(
+        &mut self,
+        address_type: ProgrammableAddress,
+        new_address: u8,
+    ) -> Result<(), Error<E>> {
+        self.set_programmable_address(address_type, new_address)?;
+        self.enable_programmable_address(address_type)?;
+        self.set_address(new_address)
+    }
+
+    /// Check whether the device responds to the LED all call address.
+    ///
+    /// This reads the `MODE1` register from the device so it reflects the
+    /// actual hardware state, for example to verify that a broadcast will
+    /// reach the device. The all call address is enabled at power-on and
+    /// can be changed with `enable_programmable_address(ProgrammableAddress::AllCall)`
+    /// and `disable_programmable_address(ProgrammableAddress::AllCall)`.
+    pub fn is_all_call_enabled(&mut self) -> Result<bool, Error<E>> {
+        let mode1 = self.read_register(Register::MODE1)?;
+        Ok(mode1 & BitFlagMode1::AllCall as u8 != 0)
+    }
+
     /// Sets the address used by the driver for communication.
     ///
     /// This does not have any effect on the hardware and is useful when
@@ -187,6 +488,49 @@ where
         self.write_mode2(config)
     }
 
+    /// Run `f` with the outputs changing on the I2C STOP condition.
+    ///
+    /// If the output change behavior is `OutputStateChange::OnAck`, it is
+    /// switched to `OutputStateChange::OnStop` before calling `f` and
+    /// restored afterwards, even if `f` fails. Otherwise `f` is just called.
+    /// With `OnAck`, a channel changes as soon as its four registers are
+    /// written, so the channels of a multi-channel write like
+    /// `write_channels_from()` change one after another. With `OnStop`, all
+    /// registers written by a single method call take effect together at the
+    /// STOP condition ending the transfer.
+    ///
+    /// Note that every method call inside `f` still ends with its own STOP
+    /// condition, so separate calls take effect separately. Use
+    /// `transaction()` to make several channel changes take effect at once.
+    ///
+    /// Returns the result of `f`, or the error restoring the output change
+    /// behavior if only that failed.
+    ///
+    /// ```no_run
+    /// use linux_embedded_hal::I2cdev;
+    /// use pwm_pca9685::{Address, Channel, Pca9685};
+    ///
+    /// let dev = I2cdev::new("/dev/i2c-1").unwrap();
+    /// let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+    /// pwm.with_synchronized_output(|pwm| {
+    ///     pwm.write_channels_from(Channel::C0, &[(0, 1024), (0, 2048), (0, 3072)])
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn with_synchronized_output<F, R>(&mut self, f: F) -> Result<R, Error<E>>
+    where
+        F: FnOnce(&mut Self) -> Result<R, Error<E>>,
+    {
+        if self.config.is_low(BitFlagMode2::Och) {
+            return f(self);
+        }
+        self.set_output_change_behavior(OutputStateChange::OnStop)?;
+        let result = f(self);
+        let restored = self.set_output_change_behavior(OutputStateChange::OnAck);
+        let value = result?;
+        restored.map(|_| value)
+    }
+
     /// Set the output driver configuration.
     pub fn set_output_driver(&mut self, driver: OutputDriver) -> Result<(), Error<E>> {
         let config = match driver {
@@ -229,15 +573,142 @@ where
         }
     }
 
+    /// Read the output configuration from the `MODE2` register.
+    ///
+    /// This reads the register from the device so it reflects the actual
+    /// hardware state, even if it was changed by someone else.
+    pub fn get_output_config(&mut self) -> Result<OutputConfig, Error<E>> {
+        let mode2 = self.read_register(Register::MODE2)?;
+        let config = Config {
+            mode1: self.config.mode1,
+            mode2,
+        };
+        Ok(config.output_config())
+    }
+
+    /// Check whether the outputs are configured as open-drain.
+    ///
+    /// This reads the `OUTDRV` bit of the `MODE2` register from the device.
+    /// Returns `false` if the outputs use the totem pole structure, which is
+    /// the power-on setting.
+    pub fn is_open_drain(&mut self) -> Result<bool, Error<E>> {
+        let mode2 = self.read_register(Register::MODE2)?;
+        Ok(mode2 & BitFlagMode2::OutDrv as u8 == 0)
+    }
+
+    /// Describe the configuration of the device in one human-readable line.
+    ///
+    /// This reads the `MODE1`, `MODE2` and `PRE_SCALE` registers from the
+    /// device and summarizes the frequency, the output configuration and the
+    /// sleep state, for example for a status command over a serial port:
+    ///
+    /// `196.9 Hz (prescale 30), totem pole, direct output, change on STOP,
+    /// disabled output 0, sleeping`
+    ///
+    /// The frequency is computed from the prescale value read from the device
+    /// and the assumed oscillator frequency (see `oscillator_hz()`).
+    #[cfg(feature = "heapless")]
+    pub fn describe_config(&mut self) -> Result<heapless::String<128>, Error<E>> {
+        use core::fmt::Write;
+
+        let mode1 = self.read_register(Register::MODE1)?;
+        let mode2 = self.read_register(Register::MODE2)?;
+        let prescale = self.read_register(Register::PRE_SCALE)?;
+        let config = Config { mode1, mode2 };
+        let output = config.output_config();
+        let driver = match output.driver {
+            OutputDriver::TotemPole => "totem pole",
+            OutputDriver::OpenDrain => "open-drain",
+        };
+        let invert = match output.invert {
+            OutputLogicState::Direct => "direct",
+            OutputLogicState::Inverted => "inverted",
+        };
+        let change = match output.change {
+            OutputStateChange::OnStop => "STOP",
+            OutputStateChange::OnAck => "ACK",
+        };
+        let disabled = match output.disabled_state {
+            DisabledOutputValue::Zero => "0",
+            DisabledOutputValue::OutputDriver => "driver dependent",
+            DisabledOutputValue::HighImpedance => "high-impedance",
+        };
+        let sleep = if config.is_high(BitFlagMode1::Sleep) {
+            "sleeping"
+        } else {
+            "awake"
+        };
+        let mut description = heapless::String::new();
+        // The capacity is enough for the longest possible description.
+        let _ = write!(
+            description,
+            "{:.1} Hz (prescale {}), {}, {} output, change on {}, disabled output {}, {}",
+            crate::frequency::frequency_for(self.oscillator_hz, prescale),
+            prescale,
+            driver,
+            invert,
+            change,
+            disabled,
+            sleep
+        );
+        Ok(description)
+    }
+
+    /// Set the complete output configuration at once.
+    ///
+    /// This composes the whole `MODE2` register and writes it in a single
+    /// transaction so that no intermediate configuration is ever active on
+    /// the device. The reserved `OUTNE` value `11` is never written as
+    /// `DisabledOutputValue` cannot represent it.
+    ///
+    /// The individual setters like `set_output_driver()` are still available
+    /// for incremental changes.
+    pub fn set_output_config(&mut self, config: OutputConfig) -> Result<(), Error<E>> {
+        let config = self.config.with_output_config(config);
+        self.write_mode2(config)
+    }
+
     /// Enable using the EXTCLK pin as clock source input.
     ///
+    /// `clock_hz` is the frequency of the external clock, which is used by
+    /// the frequency-related methods like `set_frequency()` from then on.
+    /// It can be at most 50 MHz. If the exact frequency turns out to be
+    /// different, this method can be called again with the corrected value.
+    ///
     /// This setting is _sticky_. It can only be cleared by a power cycle or
     /// a software reset.
-    pub fn use_external_clock(&mut self) -> Result<(), Error<E>> {
+    pub fn use_external_clock(&mut self, clock_hz: u32) -> Result<(), Error<E>> {
+        if clock_hz == 0 || clock_hz > 50_000_000 {
+            return Err(Error::InvalidInputData);
+        }
         let config = self.config;
         self.write_mode1(config.with_high(BitFlagMode1::Sleep))?;
         let config = self.config;
-        self.write_mode1(config.with_high(BitFlagMode1::ExtClk))
+        self.write_mode1(config.with_high(BitFlagMode1::ExtClk))?;
+        self.oscillator_hz = clock_hz;
+        Ok(())
+    }
+
+    /// Check whether the EXTCLK pin is used as clock source.
+    ///
+    /// This reads the `MODE1` register from the device, so it also detects
+    /// an external clock selected before a warm reboot of the controller,
+    /// since the setting is sticky. In that case the driver still assumes
+    /// the internal oscillator until `use_external_clock()` is called with
+    /// the clock frequency. With the `log` feature a warning is emitted then.
+    pub fn is_external_clock(&mut self) -> Result<bool, Error<E>> {
+        let mode1 = self.read_register(Register::MODE1)?;
+        let external = mode1 & BitFlagMode1::ExtClk as u8 != 0;
+        #[cfg(feature = "log")]
+        {
+            if external && self.config.is_low(BitFlagMode1::ExtClk) {
+                log::warn!(
+                    "PCA9685 {:#04x}: EXTCLK is set but no external clock frequency was configured",
+                    self.address
+                );
+            }
+        }
+        Ok(external)
     }
 
     /// Set the prescale value.
@@ -266,9 +737,8 @@ where
             self.write_mode1(config.with_high(BitFlagMode1::Sleep))?;
         }
 
-        self.i2c
-            .write(self.address, &[Register::PRE_SCALE, prescale])
-            .map_err(Error::I2C)?;
+        self.write_registers(&[Register::PRE_SCALE, prescale])?;
+        self.prescale = prescale;
 
         if was_oscillator_running {
             // restart the oscillator
@@ -277,11 +747,87 @@ where
         Ok(())
     }
 
+    /// Read the prescale value from the device.
+    ///
+    /// This reads the `PRE_SCALE` register so it reflects the actual
+    /// hardware state, even if it was changed by someone else.
+    pub fn get_prescale(&mut self) -> Result<u8, Error<E>> {
+        self.read_register(Register::PRE_SCALE)
+    }
+
+    /// Check whether the device uses the power-on prescale value of 30.
+    ///
+    /// This reads the prescale value with `get_prescale()` and is useful to
+    /// verify that a software reset or `reset_to_defaults()` took effect.
+    pub fn is_at_default_frequency(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.get_prescale()? == PRESCALE_DEFAULT)
+    }
+
+    /// Write a value to any register of the device.
+    ///
+    /// This is an escape hatch for experimenting with features not covered by
+    /// this driver. The cached `MODE1`, `MODE2` and prescale values are updated
+    /// when writing those registers, but no further validation is done.
+    ///
+    /// Writing the `TestMode` register (`0xFF`) returns
+    /// `Error::ForbiddenRegister`. This register is reserved for factory
+    /// testing and the datasheet states that it must never be accessed.
+    /// Its behavior is undocumented and writing it can leave the device in
+    /// a state from which only a power cycle may recover it.
+    pub fn write_raw_register(&mut self, register: u8, value: u8) -> Result<(), Error<E>> {
+        if register == Register::TEST_MODE {
+            return Err(Error::ForbiddenRegister);
+        }
+        self.write_registers(&[register, value])?;
+        match register {
+            Register::MODE1 => self.config.mode1 = value,
+            Register::MODE2 => self.config.mode2 = value,
+            Register::PRE_SCALE => self.prescale = value,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Restore the power-on register values on this device only.
+    ///
+    /// This writes the documented power-on values: `MODE1` = `0x11`,
+    /// `MODE2` = `0x04`, all channel counters to 0 with the `full OFF`
+    /// flag set (so all outputs are off) and a prescale value of 30.
+    /// The device is put to sleep first because the prescale can only be
+    /// written while the oscillator is stopped. It stays asleep afterwards,
+    /// as after a power cycle. The internal driver state is reset as well.
+    ///
+    /// In contrast to the I2C general-call software reset (SWRST), this
+    /// only affects this device and not every PCA9685 on the bus.
+    /// Programmable addresses are not modified and the `EXTCLK` setting is
+    /// kept, since it can only be cleared by a power cycle or a SWRST.
+    pub fn reset_to_defaults(&mut self) -> Result<(), Error<E>> {
+        let default = Config::default();
+        self.write_mode1(default.with_high(BitFlagMode1::AutoInc))?;
+
+        let mut data = [0; 65];
+        data[0] = Register::C0_ON_L;
+        for off_h in data.iter_mut().skip(4).step_by(4) {
+            *off_h = 0x10;
+        }
+        // The channels are written while asleep on purpose.
+        let strict_sleep = self.strict_sleep;
+        self.strict_sleep = false;
+        let result = self.write_registers(&data);
+        self.strict_sleep = strict_sleep;
+        result?;
+        self.muted = [None; CHANNEL_COUNT];
+        self.write_registers(&[Register::PRE_SCALE, PRESCALE_DEFAULT])?;
+        self.prescale = PRESCALE_DEFAULT;
+        self.write_mode2(default)?;
+        self.write_mode1(default)
+    }
+
     /// Reset the internal state of this driver to the default values.
     ///
     /// *Note:* This does not alter the state or configuration of the device.
     ///
-    /// This resets the cached configuration register value in this driver to
+    /// This resets the cached configuration register values in this driver to
     /// the power-up (reset) configuration of the device.
     ///
     /// This needs to be called after performing a reset on the device, for
@@ -290,5 +836,63 @@ where
     /// and in the driver match.
     pub fn reset_internal_driver_state(&mut self) {
         self.config = Config::default();
+        self.prescale = PRESCALE_DEFAULT;
+        self.oscillator_hz = self.internal_oscillator_hz;
+        self.muted = [None; CHANNEL_COUNT];
+    }
+
+    /// Initialize the device only if it does not seem to be configured yet.
+    ///
+    /// This reads `MODE1`, `MODE2` and the prescale value from the device.
+    /// If the device is sleeping, as after a power cycle, the power-on
+    /// defaults are applied with `reset_to_defaults()` and `true` is
+    /// returned.
+    ///
+    /// Otherwise the device is assumed to be running a configuration set up
+    /// before, for example before a soft reboot of the microcontroller.
+    /// Nothing is written, so the outputs do not glitch, and the read values
+    /// are adopted as the cached configuration of this driver.
+    /// Returns `false` in this case.
+    ///
+    /// The `EXTCLK` setting survives both cases since it is sticky. If it is
+    /// set, `external_clock_hz` must be the frequency of the external clock
+    /// as given to `use_external_clock()` and it is used by the frequency
+    /// calculations from then on. It is ignored if the device uses the
+    /// internal oscillator.
+    ///
+    /// Returns `Error::InvalidInputData` if the device uses the `EXTCLK` pin
+    /// but `external_clock_hz` is `None`, or if `external_clock_hz` is 0 or
+    /// greater than 50 MHz. Nothing is written to the device in that case.
+    pub fn init_if_needed(&mut self, external_clock_hz: Option<u32>) -> Result<bool, Error<E>> {
+        if let Some(clock_hz) = external_clock_hz {
+            if clock_hz == 0 || clock_hz > 50_000_000 {
+                return Err(Error::InvalidInputData);
+            }
+        }
+        let mode1 = self.read_register(Register::MODE1)?;
+        let mode2 = self.read_register(Register::MODE2)?;
+        let prescale = self.read_register(Register::PRE_SCALE)?;
+        let config = Config { mode1, mode2 };
+        let external_clock_hz = if config.is_high(BitFlagMode1::ExtClk) {
+            Some(external_clock_hz.ok_or(Error::InvalidInputData)?)
+        } else {
+            None
+        };
+        let needed = config.is_high(BitFlagMode1::Sleep);
+        if needed {
+            self.reset_to_defaults()?;
+        } else {
+            // Never write the RESTART bit back by accident.
+            self.config = config.with_low(BitFlagMode1::Restart);
+            self.prescale = prescale;
+            self.last_off = [None; CHANNEL_COUNT];
+            self.last_frame = None;
+            self.muted = [None; CHANNEL_COUNT];
+        }
+        if let Some(clock_hz) = external_clock_hz {
+            self.config = self.config.with_high(BitFlagMode1::ExtClk);
+            self.oscillator_hz = clock_hz;
+        }
+        Ok(needed)
     }
 }
@@ -0,0 +1,87 @@
+/// Linear fade between two frames of channel values.
+///
+/// The frames contain the `ON` and `OFF` values in register order
+/// `[C0 ON, C0 OFF, C1 ON, ...]` as used by [`Pca9685::push_frame()`].
+/// Every call to `next_frame()` returns the next of `steps` frames, the last
+/// one being `to`. Writing the frames, for example from a timer, is left to
+/// the caller, so the fade runs as fast as the frames are pushed.
+///
+/// The 12-bit counters of the intermediate frames are interpolated
+/// linearly and rounded to the nearest value, with the `full ON/OFF` flags
+/// cleared. Only the last frame contains the flags of `to`. A channel
+/// fading out of the `full OFF` state, whose counter is usually 0, thus
+/// fades in smoothly.
+///
+/// ```no_run
+/// use linux_embedded_hal::I2cdev;
+/// use pwm_pca9685::{Address, FrameFade, Pca9685};
+///
+/// let dev = I2cdev::new("/dev/i2c-1").unwrap();
+/// let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+/// pwm.enable().unwrap();
+/// // Fade all channels in to 50% over 100 frames.
+/// let mut to = [0; 32];
+/// for off in to.iter_mut().skip(1).step_by(2) {
+///     *off = 2048;
+/// }
+/// let mut fade = FrameFade::new([0; 32], to, 100);
+/// while let Some(frame) = fade.next_frame() {
+///     pwm.push_frame(&frame).unwrap();
+///     // wait for the next tick
+/// }
+/// ```
+///
+/// [`Pca9685::push_frame()`]: crate::Pca9685::push_frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameFade {
+    from: [u16; 32],
+    to: [u16; 32],
+    steps: u16,
+    step: u16,
+}
+
+impl FrameFade {
+    /// Create a fade from `from` to `to` in `steps` frames.
+    ///
+    /// `from` itself is not returned. A `steps` of 0 is treated as 1.
+    pub fn new(from: [u16; 32], to: [u16; 32], steps: u16) -> Self {
+        FrameFade {
+            from,
+            to,
+            steps: steps.max(1),
+            step: 0,
+        }
+    }
+
+    /// Get the next frame, or `None` once `to` has been returned.
+    pub fn next_frame(&mut self) -> Option<[u16; 32]> {
+        if self.step >= self.steps {
+            return None;
+        }
+        self.step += 1;
+        if self.step == self.steps {
+            return Some(self.to);
+        }
+        let (k, n) = (u32::from(self.step), u32::from(self.steps));
+        let mut frame = [0; 32];
+        for ((value, from), to) in frame.iter_mut().zip(&self.from).zip(&self.to) {
+            let (from, to) = (u32::from(from & 0x0FFF), u32::from(to & 0x0FFF));
+            // The result is between `from` and `to` so it fits.
+            *value = ((from * (n - k) + to * k + n / 2) / n) as u16;
+        }
+        Some(frame)
+    }
+
+    /// Get the number of frames still to be returned.
+    pub fn remaining(&self) -> u16 {
+        self.steps - self.step
+    }
+}
+
+impl Iterator for FrameFade {
+    type Item = [u16; 32];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame()
+    }
+}
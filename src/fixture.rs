@@ -0,0 +1,115 @@
+use crate::{
+    brightness::level_to_counts,
+    channels::{pulse_registers, snap_min_on},
+    hal, Channel, Error, Pca9685,
+};
+
+/// An RGBW LED fixture connected to four channels.
+///
+/// The colors are set as 8-bit levels, which are optionally corrected for
+/// the nonlinear brightness perception of the human eye with a gamma curve.
+///
+/// ```no_run
+/// use linux_embedded_hal::I2cdev;
+/// use pwm_pca9685::{Address, Channel, Pca9685, RgbwFixture};
+///
+/// let dev = I2cdev::new("/dev/i2c-1").unwrap();
+/// let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+/// let fixture = RgbwFixture::new(Channel::C0, Channel::C1, Channel::C2, Channel::C3).with_gamma(2.2);
+/// // orange
+/// fixture.set_color(&mut pwm, (255, 128, 0, 0)).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RgbwFixture {
+    /// Channel of the red component
+    pub r: Channel,
+    /// Channel of the green component
+    pub g: Channel,
+    /// Channel of the blue component
+    pub b: Channel,
+    /// Channel of the white component
+    pub w: Channel,
+    /// Exponent of the gamma correction applied to the levels, if any
+    pub gamma: Option<f32>,
+}
+
+impl RgbwFixture {
+    /// Create a fixture on the given channels without gamma correction.
+    pub fn new(r: Channel, g: Channel, b: Channel, w: Channel) -> Self {
+        RgbwFixture {
+            r,
+            g,
+            b,
+            w,
+            gamma: None,
+        }
+    }
+
+    /// Apply a gamma correction with the given exponent to the levels.
+    ///
+    /// A level of `l` then results in a duty cycle of `(l / 255) ^ gamma`.
+    /// A typical value for LEDs is 2.2.
+    pub fn with_gamma(self, gamma: f32) -> Self {
+        RgbwFixture {
+            gamma: Some(gamma),
+            ..self
+        }
+    }
+
+    /// Set the levels of the red, green, blue and white components.
+    ///
+    /// A level of 0 sets the `full OFF` flag and a level of 255 sets the
+    /// `full ON` flag of the channel. The pulses start at the `ON` offset of
+    /// each channel, see [`Pca9685::set_channel_on_offset()`].
+    ///
+    /// Channels which are next to each other are written in a single
+    /// transaction with [`Pca9685::write_channels_from()`], so a fixture on
+    /// four consecutive channels is updated at once.
+    ///
+    /// Returns `Error::InvalidInputData` if any of the channels is
+    /// `Channel::All`, if a channel is used for several components or if the
+    /// gamma exponent is not a positive number.
+    pub fn set_color<I2C, E>(
+        &self,
+        pwm: &mut Pca9685<I2C>,
+        rgbw: (u8, u8, u8, u8),
+    ) -> Result<(), Error<E>>
+    where
+        I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+    {
+        if let Some(gamma) = self.gamma {
+            if gamma.is_nan() || gamma <= 0.0 {
+                return Err(Error::InvalidInputData);
+            }
+        }
+        let mut components = [
+            (self.r, rgbw.0),
+            (self.g, rgbw.1),
+            (self.b, rgbw.2),
+            (self.w, rgbw.3),
+        ];
+        components.sort_unstable_by_key(|(channel, _)| *channel as usize);
+        if components[3].0 == Channel::All
+            || components.windows(2).any(|pair| pair[0].0 == pair[1].0)
+        {
+            return Err(Error::InvalidInputData);
+        }
+        let mut values = [(0, 0); 4];
+        for (value, &(channel, level)) in values.iter_mut().zip(&components) {
+            let counts = level_to_counts(level, self.gamma);
+            let index = channel as usize;
+            *value = pulse_registers(
+                pwm.on_offsets[index],
+                snap_min_on(counts, pwm.min_on[index]),
+            );
+        }
+        let mut start = 0;
+        for end in 1..=4 {
+            if end == 4 || components[end].0 as usize != components[end - 1].0 as usize + 1 {
+                pwm.write_channels_from(components[start].0, &values[start..end])?;
+                start = end;
+            }
+        }
+        Ok(())
+    }
+}
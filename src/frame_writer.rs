@@ -0,0 +1,240 @@
+use crate::{
+    hal, nb,
+    register_access::{
+        channel_on_register, counter_bytes, CHANNEL_BLOCK_LEN, CHANNEL_COUNT, CHANNEL_REGISTERS,
+    },
+    Channel, Error, Pca9685,
+};
+use core::convert::TryFrom;
+
+/// Compare two frames and return a bit mask of the channels that differ.
+///
+/// The frames contain the `ON` and `OFF` values in register order
+/// `[C0 ON, C0 OFF, C1 ON, ...]`, as returned by
+/// [`Pca9685::get_all_channels_on_off_with_flags()`]. Bit `n` of the result
+/// is set if the `ON` or the `OFF` value of channel `n` changed.
+///
+/// ```
+/// let prev = [0; 32];
+/// let mut next = [0; 32];
+/// next[3] = 2047; // C1 OFF
+/// assert_eq!(0b10, pwm_pca9685::diff_frames(&prev, &next));
+/// ```
+pub fn diff_frames(prev: &[u16; 32], next: &[u16; 32]) -> u16 {
+    let mut changed = 0;
+    for (channel, (prev, next)) in prev.chunks(2).zip(next.chunks(2)).enumerate() {
+        if prev != next {
+            changed |= 1 << channel;
+        }
+    }
+    changed
+}
+
+/// Writes the counters of all channels in several steps.
+///
+/// Updating all channels in one write transfers 65 bytes, which can block
+/// for several milliseconds on a slow bus. A `FrameWriter` instead writes a
+/// few channels each time `poll()` is called, so that a cooperative
+/// scheduler can do other work between the steps. Each step is done with
+/// [`Pca9685::write_channels_from()`].
+///
+/// Note that the outputs are updated as the steps are written, so in the
+/// meantime some channels already use the new values and others do not.
+///
+/// ```no_run
+/// use linux_embedded_hal::I2cdev;
+/// use pwm_pca9685::{nb::block, Address, FrameWriter, Pca9685};
+///
+/// let dev = I2cdev::new("/dev/i2c-1").unwrap();
+/// let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+/// let mut writer = FrameWriter::new([(0, 2047); 16], 4);
+/// block!(writer.poll(&mut pwm)).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameWriter {
+    frame: [(u16, u16); 16],
+    channels_per_poll: usize,
+    next: usize,
+}
+
+impl FrameWriter {
+    /// Create a writer for the `(on, off)` counters of each channel.
+    ///
+    /// The index of the value in the array corresponds to the channel: 0-15.
+    /// Every call to `poll()` writes up to `channels_per_poll` channels.
+    /// At least one channel is written per call.
+    pub fn new(frame: [(u16, u16); 16], channels_per_poll: u8) -> Self {
+        FrameWriter {
+            frame,
+            channels_per_poll: usize::from(channels_per_poll).max(1),
+            next: 0,
+        }
+    }
+
+    /// Write the next channels.
+    ///
+    /// Returns `nb::Error::WouldBlock` while there are channels left to
+    /// write and `Ok(())` once all of them have been written. Polling again
+    /// after that does nothing.
+    ///
+    /// If writing fails, the error is returned and the same channels are
+    /// written again on the next call.
+    pub fn poll<I2C, E>(&mut self, pwm: &mut Pca9685<I2C>) -> nb::Result<(), Error<E>>
+    where
+        I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+    {
+        if self.is_done() {
+            return Ok(());
+        }
+        let end = (self.next + self.channels_per_poll).min(16);
+        let start = Channel::try_from(self.next).unwrap_or(Channel::All);
+        pwm.write_channels_from(start, &self.frame[self.next..end])
+            .map_err(nb::Error::Other)?;
+        self.next = end;
+        if self.is_done() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Whether all channels have been written.
+    pub fn is_done(&self) -> bool {
+        self.next == 16
+    }
+}
+
+/// Bus traffic of the channel writes, for bandwidth budgeting.
+///
+/// The figures are the bytes written after the I²C address byte. The first
+/// write of several registers at once is preceded by a 2-byte write
+/// enabling auto-increment in `MODE1`, which is done only once.
+impl Pca9685<()> {
+    /// Number of bytes written by `push_frame()` for a complete frame: 65.
+    ///
+    /// ```
+    /// use pwm_pca9685::Pca9685;
+    ///
+    /// const FRAME_BYTES: usize = Pca9685::frame_bytes();
+    /// assert_eq!(65, FRAME_BYTES);
+    /// ```
+    pub const fn frame_bytes() -> usize {
+        Self::channels_write_bytes(CHANNEL_COUNT)
+    }
+
+    /// Number of bytes written to set `channels` consecutive channels in a
+    /// single transfer.
+    ///
+    /// This is the register address plus 4 bytes per channel, for example 5
+    /// for `set_channel_on_off()` or `set_channel_duty_cycle()` and
+    /// `1 + 4 * values.len()` for `write_channels_from()`.
+    pub const fn channels_write_bytes(channels: usize) -> usize {
+        1 + channels * CHANNEL_REGISTERS
+    }
+}
+
+impl<I2C, E> Pca9685<I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    /// Enable or disable keeping the last frame given to `push_frame()`.
+    ///
+    /// The kept frame is written again by `service_watchdog()` after a
+    /// restart. Other writes to the channel registers are not tracked, so
+    /// channels changed with other methods since then are overwritten.
+    ///
+    /// This is disabled by default. Changing the setting forgets the frame.
+    pub fn set_frame_retention(&mut self, enabled: bool) {
+        self.retain_frame = enabled;
+        self.retained_frame = None;
+    }
+
+    /// Write a complete frame of channel values, for example once per frame
+    /// of an animation.
+    ///
+    /// The frame contains the `ON` and `OFF` values in register order
+    /// `[C0 ON, C0 OFF, C1 ON, ...]`, each with the 12-bit counter and the
+    /// `full ON/OFF` flag in bit 12, as returned by
+    /// `get_all_channels_on_off_with_flags()`. This is the fast path for
+    /// frequent updates: the values are not validated, any bits above bit
+    /// 12 are simply ignored, and all channels are written in a single
+    /// auto-incremented transfer of 65 bytes.
+    ///
+    /// If deduplication is enabled with `set_dedup()`, only the range of
+    /// channels from the first to the last channel changed since the
+    /// previous call is written, or nothing at all if the frame did not
+    /// change. Any other write to the channel registers makes the driver
+    /// forget the previous frame.
+    ///
+    /// If frame retention is enabled with `set_frame_retention()`, the frame
+    /// is kept for `service_watchdog()`.
+    pub fn push_frame(&mut self, frame: &[u16; 32]) -> Result<(), Error<E>> {
+        if self.retain_frame {
+            self.retained_frame = Some(*frame);
+        }
+        let mut physical = [0; 2 * CHANNEL_COUNT];
+        for logical in 0..CHANNEL_COUNT {
+            let i = self.physical_index(logical) * 2;
+            physical[i] = frame[logical * 2];
+            physical[i + 1] = frame[logical * 2 + 1];
+        }
+        let changed = match self.last_frame {
+            Some(last) if self.dedup => diff_frames(&last, &physical),
+            _ => 0xFFFF,
+        };
+        if changed == 0 {
+            return Ok(());
+        }
+        let first = changed.trailing_zeros() as usize;
+        let last = 15 - changed.leading_zeros() as usize;
+        let mut data = [0; CHANNEL_BLOCK_LEN + 1];
+        data[0] = channel_on_register(first);
+        let values = &physical[first * 2..(last + 1) * 2];
+        for (bytes, value) in data[1..].chunks_mut(2).zip(values) {
+            bytes.copy_from_slice(&counter_bytes(*value));
+        }
+        self.enable_auto_increment()?;
+        self.write_registers(&data[..values.len() * 2 + 1])?;
+        if self.dedup {
+            self.last_frame = Some(physical);
+        }
+        Ok(())
+    }
+
+    /// Write a complete frame of channel values and verify it by reading it
+    /// back.
+    ///
+    /// The frame is written with `push_frame()` and then all channels are
+    /// read back with `get_all_channels_on_off_with_flags()`, so this
+    /// doubles the bus traffic. It is meant for outputs where a corrupted
+    /// transfer, for example over a long noisy cable, must be detected.
+    /// Only the 12-bit counters are compared, the full ON/OFF flags and the
+    /// bits above them are masked.
+    ///
+    /// The registers are read back right after writing them, so unlike
+    /// `set_frequency_verified()` this does not take a delay.
+    ///
+    /// Returns `Error::VerificationFailed` with the first channel whose `ON`
+    /// or `OFF` counter does not match. The previous frame used for
+    /// deduplication is forgotten then, so the next frame is written
+    /// completely.
+    pub fn push_frame_verified(&mut self, frame: &[u16; 32]) -> Result<(), Error<E>> {
+        self.push_frame(frame)?;
+        let read = self.get_all_channels_on_off_with_flags()?;
+        let mismatch = frame
+            .chunks(2)
+            .zip(read.chunks(2))
+            .position(|(written, read)| {
+                written
+                    .iter()
+                    .zip(read)
+                    .any(|(w, r)| w & 0x0FFF != r & 0x0FFF)
+            });
+        if let Some(index) = mismatch {
+            self.last_frame = None;
+            let channel = Channel::try_from(index).unwrap_or(Channel::All);
+            return Err(Error::VerificationFailed(Some(channel)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+use crate::{
+    config::BitFlagMode1,
+    hal::{self, blocking::delay::DelayUs},
+    Error, Pca9685, Register,
+};
+
+/// Frequency of the internal oscillator.
+pub(crate) const INTERNAL_OSCILLATOR_HZ: u32 = 25_000_000;
+/// Minimum valid prescale value.
+pub(crate) const PRESCALE_MIN: u8 = 3;
+/// Power-on prescale value. Corresponds to about 200 Hz.
+pub(crate) const PRESCALE_DEFAULT: u8 = 0x1E;
+
+/// Compute the lowest PWM frequency at which one counter tick lasts at most
+/// `res_us` microseconds.
+///
+/// The period is always divided into 4096 ticks, so a tick lasts
+/// `1 / (4096 * frequency)` independently of the oscillator frequency. Any
+/// frequency at or above the returned one achieves the requested
+/// resolution. For example, a resolution of 0.5 µs requires at least
+/// about 488 Hz:
+///
+/// ```
+/// use pwm_pca9685::min_frequency_for_resolution_us;
+///
+/// let hz = min_frequency_for_resolution_us(0.5);
+/// assert!((hz - 488.28).abs() < 0.01);
+/// ```
+///
+/// The device supports frequencies from about 24 Hz to 1526 Hz and the
+/// prescale value actually used may yield a slightly lower frequency than
+/// requested. Check the result with `pulse_resolution_us()` after setting
+/// the frequency.
+///
+/// Returns NaN if `res_us` is not positive.
+pub fn min_frequency_for_resolution_us(res_us: f32) -> f32 {
+    if res_us.is_nan() || res_us <= 0.0 {
+        return core::f32::NAN;
+    }
+    1_000_000.0 / (4096.0 * res_us)
+}
+
+impl<I2C, E> Pca9685<I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    /// Set the PWM frequency.
+    ///
+    /// This computes the prescale value for `hz` with the frequency of the
+    /// clock source (see `oscillator_hz()`) and writes it with
+    /// `set_prescale()`. Use `frequency_error()` to find out how close the
+    /// achieved frequency is.
+    ///
+    /// The channel counters are not modified. Since they count ticks, which
+    /// are always 1/4096 of the period, the duty cycle of every channel is
+    /// preserved but the duration of the pulses changes with the period.
+    /// See `set_frequency_preserve_fraction()` for an example.
+    ///
+    /// Returns `Error::InvalidInputData` if the requested frequency is out of
+    /// the achievable range (prescale values 3-255).
+    pub fn set_frequency(&mut self, hz: f32) -> Result<(), Error<E>> {
+        let prescale = prescale_for(self.oscillator_hz, hz).ok_or(Error::InvalidInputData)?;
+        self.set_prescale(prescale)
+    }
+
+    /// Set the PWM frequency preserving the duty cycle of every channel.
+    ///
+    /// This is the same as `set_frequency()`, which already preserves the
+    /// duty cycles because it does not modify the channel counters. The
+    /// duration of the pulses changes with the period, though. For servos,
+    /// which depend on the pulse duration, the pulses need to be set again
+    /// after changing the frequency, for example with `set_channel_pulse_us()`.
+    ///
+    /// ```
+    /// use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction};
+    /// use pwm_pca9685::{Address, Pca9685};
+    ///
+    /// let trans = [
+    ///     Transaction::write(0x40, vec![0xFE, 121]),
+    ///     Transaction::write(0x40, vec![0xFE, 60]),
+    /// ];
+    /// let mut pwm = Pca9685::new(I2cMock::new(&trans), Address::default()).unwrap();
+    ///
+    /// // A channel with a duty cycle of 50%
+    /// let counts = 2048.0;
+    /// pwm.set_frequency(50.0).unwrap();
+    /// let width_us_at_50_hz = counts * pwm.pulse_resolution_us();
+    ///
+    /// pwm.set_frequency_preserve_fraction(100.0).unwrap();
+    /// let width_us_at_100_hz = counts * pwm.pulse_resolution_us();
+    ///
+    /// // The duty cycle is still 50% but the pulse is half as long.
+    /// assert!((width_us_at_50_hz / width_us_at_100_hz - 2.0).abs() < 0.01);
+    /// pwm.destroy().done();
+    /// ```
+    pub fn set_frequency_preserve_fraction(&mut self, hz: f32) -> Result<(), Error<E>> {
+        self.set_frequency(hz)
+    }
+
+    /// Set the PWM period in nanoseconds.
+    ///
+    /// This computes the prescale value for the frequency corresponding to
+    /// `ns` and writes it with `set_prescale()`. If the oscillator was
+    /// running, this waits 500us after restarting it so that it is stable
+    /// again when this method returns.
+    ///
+    /// Returns `Error::InvalidInputData` if the requested period is out of
+    /// the achievable range (prescale values 3-255), that is about 0.66 ms
+    /// to 42 ms with the internal oscillator.
+    pub fn set_period_ns(
+        &mut self,
+        ns: u32,
+        delay: &mut impl DelayUs<u16>,
+    ) -> Result<(), Error<E>> {
+        if ns == 0 {
+            return Err(Error::InvalidInputData);
+        }
+        let hz = 1_000_000_000.0 / ns as f32;
+        let prescale = prescale_for(self.oscillator_hz, hz).ok_or(Error::InvalidInputData)?;
+        self.set_prescale_and_wait(prescale, delay)
+    }
+
+    /// Run `f` at a different PWM frequency and restore the previous one
+    /// afterwards.
+    ///
+    /// The current prescale value is read from the device with
+    /// `get_prescale()`, then the frequency is changed to `hz` as with
+    /// `set_frequency()` and `f` is called. Afterwards the previous prescale
+    /// value is written back, even if `f` failed. If the oscillator is
+    /// running, this waits 500us after each change so that it is stable
+    /// when `f` is called and when this method returns.
+    ///
+    /// Returns the result of `f`, or the error restoring the prescale value
+    /// if only that failed. Returns `Error::InvalidInputData` without
+    /// changing anything if the requested frequency is out of the achievable
+    /// range (prescale values 3-255).
+    pub fn with_frequency<D, F, R>(&mut self, hz: f32, delay: &mut D, f: F) -> Result<R, Error<E>>
+    where
+        D: DelayUs<u16>,
+        F: FnOnce(&mut Self) -> Result<R, Error<E>>,
+    {
+        let prescale = prescale_for(self.oscillator_hz, hz).ok_or(Error::InvalidInputData)?;
+        let previous = self.get_prescale()?;
+        self.set_prescale_and_wait(prescale, delay)?;
+        let result = f(self);
+        let restored = self.set_prescale_and_wait(previous, delay);
+        let value = result?;
+        restored.map(|_| value)
+    }
+
+    /// Set the PWM frequency and all channels, then enable the controller.
+    ///
+    /// The controller is put to sleep if it was running, the prescale value
+    /// for `hz` is written as with `set_frequency()` and all channels are
+    /// preloaded with `push_frame()`. Only then is the controller enabled,
+    /// waiting 500us for the oscillator to be stable. This way the outputs
+    /// start at the desired frequency with the desired pulses, without
+    /// running at the previous configuration in between, for example at
+    /// power-on. The channels are written even if strict sleep mode is
+    /// enabled with `set_strict_sleep()`.
+    ///
+    /// Returns `Error::InvalidInputData` without writing anything if the
+    /// requested frequency is out of the achievable range (prescale values
+    /// 3-255).
+    pub fn configure(
+        &mut self,
+        hz: f32,
+        frame: &[u16; 32],
+        delay: &mut impl DelayUs<u16>,
+    ) -> Result<(), Error<E>> {
+        let prescale = prescale_for(self.oscillator_hz, hz).ok_or(Error::InvalidInputData)?;
+        if self.config.is_low(BitFlagMode1::Sleep) {
+            self.disable()?;
+        }
+        self.set_prescale(prescale)?;
+        // The channels are written while asleep on purpose.
+        let strict_sleep = self.strict_sleep;
+        self.strict_sleep = false;
+        let result = self.push_frame(frame);
+        self.strict_sleep = strict_sleep;
+        result?;
+        self.enable()?;
+        delay.delay_us(500_u16);
+        Ok(())
+    }
+
+    /// Set the prescale value and wait for the oscillator to be stable
+    /// again if it was running.
+    fn set_prescale_and_wait(
+        &mut self,
+        prescale: u8,
+        delay: &mut impl DelayUs<u16>,
+    ) -> Result<(), Error<E>> {
+        let was_oscillator_running = self.config.is_low(BitFlagMode1::Sleep);
+        self.set_prescale(prescale)?;
+        if was_oscillator_running {
+            delay.delay_us(500_u16);
+        }
+        Ok(())
+    }
+
+    /// Get the PWM frequency.
+    ///
+    /// This is computed from the prescale value last set through this driver
+    /// and the frequency of the clock source and does not communicate with
+    /// the device.
+    pub fn get_frequency(&self) -> f32 {
+        frequency_for(self.oscillator_hz, self.prescale)
+    }
+
+    /// Get the frequency of the clock source assumed in the frequency
+    /// calculations.
+    ///
+    /// This is the internal oscillator frequency, 25 MHz unless set with
+    /// `set_oscillator_frequency()`, or the value passed to
+    /// `use_external_clock()`.
+    pub fn oscillator_hz(&self) -> u32 {
+        self.oscillator_hz
+    }
+
+    /// Set the measured frequency of the internal oscillator.
+    ///
+    /// The internal oscillator has a tolerance of a few percent. After
+    /// measuring the actual output frequency once, the oscillator frequency
+    /// can be corrected with this so that all frequency, prescale and pulse
+    /// duration calculations compensate for it. The default is 25 MHz.
+    ///
+    /// This does not communicate with the device. The correction is kept
+    /// when resetting the internal driver state. If the external clock is
+    /// in use, it only takes effect after switching back to the internal
+    /// oscillator.
+    ///
+    /// Returns `Error::InvalidInputData` if `measured_hz` is 0.
+    pub fn set_oscillator_frequency(&mut self, measured_hz: u32) -> Result<(), Error<E>> {
+        if measured_hz == 0 {
+            return Err(Error::InvalidInputData);
+        }
+        self.internal_oscillator_hz = measured_hz;
+        if self.config.is_low(BitFlagMode1::ExtClk) {
+            self.oscillator_hz = measured_hz;
+        }
+        Ok(())
+    }
+
+    /// Set the PWM frequency, verify that it was applied and return the
+    /// achieved frequency.
+    ///
+    /// This computes the prescale value for `hz`, writes it as
+    /// `set_prescale()` does and wakes the device up if it was sleeping.
+    /// After waiting 500us for the oscillator to stabilize, the prescale
+    /// value is read back from the device.
+    ///
+    /// Returns `Error::InvalidInputData` if the requested frequency is out of
+    /// the achievable range (prescale values 3-255) and
+    /// `Error::VerificationFailed(None)` if the read back prescale value does
+    /// not match the written one.
+    pub fn set_frequency_verified(
+        &mut self,
+        hz: f32,
+        delay: &mut impl DelayUs<u16>,
+    ) -> Result<f32, Error<E>> {
+        let osc = self.oscillator_hz;
+        let prescale = prescale_for(osc, hz).ok_or(Error::InvalidInputData)?;
+        self.set_prescale(prescale)?;
+        if self.config.is_high(BitFlagMode1::Sleep) {
+            self.enable()?;
+        }
+        delay.delay_us(500_u16);
+        if self.read_register(Register::PRE_SCALE)? != prescale {
+            return Err(Error::VerificationFailed(None));
+        }
+        Ok(frequency_for(osc, prescale))
+    }
+
+    /// Compute the relative error between a requested PWM frequency and the
+    /// frequency that can actually be achieved.
+    ///
+    /// The prescale value is quantized, so only certain frequencies can be
+    /// generated. This computes the prescale value that `requested_hz` would
+    /// be rounded to and returns `(achieved - requested) / requested`.
+    /// A positive value means the output will run faster than requested.
+    ///
+    /// Nothing is written to the device.
+    ///
+    /// Returns `Error::InvalidInputData` if the requested frequency is out of
+    /// the achievable range (prescale values 3-255).
+    pub fn frequency_error(&self, requested_hz: f32) -> Result<f32, Error<E>> {
+        let osc = self.oscillator_hz;
+        let prescale = prescale_for(osc, requested_hz).ok_or(Error::InvalidInputData)?;
+        let achieved = frequency_for(osc, prescale);
+        Ok((achieved - requested_hz) / requested_hz)
+    }
+
+    /// Get the duration of one counter tick in microseconds at the current
+    /// frequency.
+    ///
+    /// This is the finest step in which a pulse length can be adjusted.
+    /// For example, at 50 Hz the period of 20 ms is divided into 4096 ticks
+    /// of about 4.9 µs each, which is what limits the angular resolution of
+    /// a servo.
+    ///
+    /// This is computed from the prescale value last set through this driver
+    /// and does not communicate with the device.
+    pub fn pulse_resolution_us(&self) -> f32 {
+        (f32::from(self.prescale) + 1.0) * 1_000_000.0 / self.oscillator_hz as f32
+    }
+
+    /// Compute the counter offset corresponding to a delay in microseconds
+    /// at the current frequency.
+    ///
+    /// The result is rounded to the nearest tick and can be passed to
+    /// `set_channel_on_offset()` to place the rising edge of a channel at a
+    /// precise instant within the period.
+    ///
+    /// This is computed from the prescale value last set through this driver
+    /// and does not communicate with the device.
+    ///
+    /// Returns `Error::InvalidInputData` if the delay is negative or if it
+    /// is not shorter than the period.
+    pub fn phase_counts_for_us(&self, delay_us: f32) -> Result<u16, Error<E>> {
+        if delay_us.is_nan() || delay_us < 0.0 {
+            return Err(Error::InvalidInputData);
+        }
+        // `round()` is not available in `core`. The value is always positive here.
+        let counts = delay_us / self.pulse_resolution_us() + 0.5;
+        if counts >= 4096.0 {
+            return Err(Error::InvalidInputData);
+        }
+        Ok(counts as u16)
+    }
+}
+
+/// Compute the prescale value for an update rate with the formula:
+/// `prescale_value = round(osc_value / (4096 * update_rate)) - 1`
+///
+/// Returns `None` if the result is not a valid prescale value.
+pub(crate) fn prescale_for(oscillator_hz: u32, frequency_hz: f32) -> Option<u8> {
+    if frequency_hz.is_nan() || frequency_hz <= 0.0 {
+        return None;
+    }
+    // `round()` is not available in `core`. The value is always positive here.
+    let prescale = (oscillator_hz as f32 / (4096.0 * frequency_hz) + 0.5) as u32;
+    if prescale < u32::from(PRESCALE_MIN) + 1 || prescale > 256 {
+        None
+    } else {
+        Some((prescale - 1) as u8)
+    }
+}
+
+/// Compute the update rate which corresponds to a prescale value.
+pub(crate) fn frequency_for(oscillator_hz: u32, prescale: u8) -> f32 {
+    oscillator_hz as f32 / (4096.0 * (f32::from(prescale) + 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prescale_for_60hz_is_101() {
+        assert_eq!(Some(101), prescale_for(INTERNAL_OSCILLATOR_HZ, 60.0));
+    }
+
+    #[test]
+    fn prescale_for_limits() {
+        assert_eq!(Some(3), prescale_for(INTERNAL_OSCILLATOR_HZ, 1526.0));
+        assert_eq!(Some(255), prescale_for(INTERNAL_OSCILLATOR_HZ, 23.85));
+    }
+
+    #[test]
+    fn prescale_for_out_of_range() {
+        assert_eq!(None, prescale_for(INTERNAL_OSCILLATOR_HZ, 2000.0));
+        assert_eq!(None, prescale_for(INTERNAL_OSCILLATOR_HZ, 23.0));
+        assert_eq!(None, prescale_for(INTERNAL_OSCILLATOR_HZ, 0.0));
+        assert_eq!(None, prescale_for(INTERNAL_OSCILLATOR_HZ, -50.0));
+        assert_eq!(None, prescale_for(INTERNAL_OSCILLATOR_HZ, f32::NAN));
+    }
+
+    #[test]
+    fn min_frequency_for_resolution() {
+        // 1 µs ticks give a period of 4096 µs
+        assert!((min_frequency_for_resolution_us(1.0) - 244.14).abs() < 0.01);
+        assert!(min_frequency_for_resolution_us(0.0).is_nan());
+        assert!(min_frequency_for_resolution_us(-1.0).is_nan());
+        assert!(min_frequency_for_resolution_us(f32::NAN).is_nan());
+    }
+
+    #[test]
+    fn frequency_for_default_prescale() {
+        let freq = frequency_for(INTERNAL_OSCILLATOR_HZ, 30);
+        assert!((freq - 196.9).abs() < 0.1);
+    }
+}
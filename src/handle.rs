@@ -0,0 +1,54 @@
+use crate::{hal, Channel, Error, Pca9685};
+
+/// Handle to a single channel of a PCA9685.
+///
+/// This mutably borrows the driver, so only one handle can be alive at a
+/// time. All methods are shortcuts to the driver methods of the same name
+/// for the selected channel.
+///
+/// Obtain it with [`Pca9685::handle()`].
+#[derive(Debug)]
+pub struct ChannelHandle<'a, I2C> {
+    pwm: &'a mut Pca9685<I2C>,
+    channel: Channel,
+}
+
+impl<I2C, E> Pca9685<I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    /// Get a handle to a single channel.
+    pub fn handle(&mut self, channel: Channel) -> ChannelHandle<'_, I2C> {
+        ChannelHandle { pwm: self, channel }
+    }
+}
+
+impl<'a, I2C, E> ChannelHandle<'a, I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    /// Get the channel of this handle.
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Set the duty cycle. See [`Pca9685::set_channel_duty_cycle()`].
+    pub fn set_duty(&mut self, duty: f32) -> Result<(), Error<E>> {
+        self.pwm.set_channel_duty_cycle(self.channel, duty)
+    }
+
+    /// Set the `ON` and `OFF` counters. See [`Pca9685::set_channel_on_off()`].
+    pub fn set_on_off(&mut self, on: u16, off: u16) -> Result<(), Error<E>> {
+        self.pwm.set_channel_on_off(self.channel, on, off)
+    }
+
+    /// Set the `full ON` flag. See [`Pca9685::set_channel_full_on()`].
+    pub fn full_on(&mut self, flag_value: bool) -> Result<(), Error<E>> {
+        self.pwm.set_channel_full_on(self.channel, flag_value)
+    }
+
+    /// Set the `full OFF` flag. See [`Pca9685::set_channel_full_off()`].
+    pub fn full_off(&mut self, flag_value: bool) -> Result<(), Error<E>> {
+        self.pwm.set_channel_full_off(self.channel, flag_value)
+    }
+}
@@ -4,21 +4,110 @@
 //! [`embedded-hal`]: https://github.com/rust-embedded/embedded-hal
 //!
 //! This driver allows you to:
+//! - Create a device which is enabled and running at a given frequency. See: [`new_with_frequency()`](Pca9685::new_with_frequency).
 //! - Enable/disable the device. See: [`enable()`](Pca9685::enable).
 //! - Set the _on_ and _off_ counter for a channel or all of them. See: [`set_channel_on()`](Pca9685::set_channel_on).
 //! - Set the _on_ and _off_ counters for a channel or all of them at once. See: [`set_channel_on_off()`](Pca9685::set_channel_on_off).
+//! - Set the _on_ counter and the pulse width of a channel. See: [`set_channel_on_and_width()`](Pca9685::set_channel_on_and_width).
 //! - Set a channel to be always on or off. See: [`set_channel_full_on()`](Pca9685::set_channel_full_on).
+//! - Set or clear the full on and off flags of all channels at once. See: [`set_all_channels_flags()`](Pca9685::set_all_channels_flags).
+//! - Set a channel to its maximum output with or without the full on flag. See: [`set_channel_max_on()`](Pca9685::set_channel_max_on).
+//! - Check whether a channel is always on or off. See: [`is_channel_fully_on()`](Pca9685::is_channel_fully_on).
+//! - Mute a channel and restore it later. See: [`mute_channel()`](Pca9685::mute_channel).
+//! - Set the brightness of a channel as raw value clearing stale full on and off flags. See: [`set_channel_value()`](Pca9685::set_channel_value).
+//! - Set the pulse length of a channel in ticks. See: [`set_channel_simple()`](Pca9685::set_channel_simple).
+//! - Invert the pulse of a channel. See: [`invert_channel()`](Pca9685::invert_channel).
+//! - Set the duty cycle of a channel. See: [`set_channel_duty_cycle()`](Pca9685::set_channel_duty_cycle).
+//! - Read the duty cycle of a channel, optionally as seen on inverted outputs. See: [`get_channel_duty_cycle_logical()`](Pca9685::get_channel_duty_cycle_logical).
+//! - Set the duty cycles of several channels at once. See: [`set_channels_duty()`](Pca9685::set_channels_duty).
+//! - Set complementary duty cycles on two channels for differential drive. See: [`set_channels_differential()`](Pca9685::set_channels_differential).
+//! - Set the duty cycle of a channel from an 8-bit PWM value. See: [`set_channel_pwm8()`](Pca9685::set_channel_pwm8).
+//! - Get and set the duty cycle of a channel as Q12 fixed-point value. See: [`set_channel_duty_q12()`](Pca9685::set_channel_duty_q12).
+//! - Get the duty cycle of a channel in permille without floating point math. See: [`get_channel_permille()`](Pca9685::get_channel_permille).
+//! - Dither the duty cycle of a channel for extra resolution. See: [`set_channel_duty_dithered()`](Pca9685::set_channel_duty_dithered).
+//! - Ramp the pulse length of a channel up and down for breathing effects. See: [`Ramp`].
+//! - Set the pulse width of a channel in microseconds. See: [`set_channel_pulse_us()`](Pca9685::set_channel_pulse_us).
+//! - Set both edges of a pulse in microseconds. See: [`set_channel_pulse_us_window()`](Pca9685::set_channel_pulse_us_window).
+//! - Move a servo to an angle or to a position given as a byte. See: [`set_servo_angle()`](Pca9685::set_servo_angle).
+//! - Move a servo to its center position. See: [`center_servo()`](Pca9685::center_servo).
+//! - Move several servos sharing a calibration by joint. See: [`ServoBank`].
+//! - Enforce a minimum pulse length per channel in the duty cycle setters. See: [`set_channel_min_on()`](Pca9685::set_channel_min_on).
+//! - Delay the pulse of a channel by an _on_ offset. See: [`set_channel_on_offset()`](Pca9685::set_channel_on_offset).
+//! - Shift the rising edges of all channels to the same phase keeping the duty cycles. See: [`set_global_phase()`](Pca9685::set_global_phase).
+//! - Set both edges of a pulse as fractions of the period. See: [`set_channel_window()`](Pca9685::set_channel_window).
 //! - Set the _on_ and _off_ counters for each channel at once. See: [`set_all_on_off()`](Pca9685::set_all_on_off).
+//! - Set the _on_ and _off_ values including the flags for each channel at once. See: [`set_all_channels_on_off_with_flags()`](Pca9685::set_all_channels_on_off_with_flags).
+//! - Apply a stored scene of channel values, optionally overriding the flags of some channels. See: [`apply_scene_with_flags()`](Pca9685::apply_scene_with_flags).
+//! - Set the PWM frequency. See: [`set_frequency()`](Pca9685::set_frequency).
+//! - Set the _on_ counter of every channel to 0 preserving the _off_ counters. See: [`zero_all_on()`](Pca9685::zero_all_on).
+//! - Set the brightness of all channels through a precomputed gamma table. See: [`set_all_channels_brightness_lut()`](Pca9685::set_all_channels_brightness_lut).
+//! - Step a channel through a stored curve of _off_ counters. See: [`apply_curve()`](Pca9685::apply_curve).
+//! - Set the pulse lengths of all channels scaled down to a total budget. See: [`set_channels_budgeted()`](Pca9685::set_channels_budgeted).
+//! - Run code at a different PWM frequency and restore the previous one afterwards. See: [`with_frequency()`](Pca9685::with_frequency).
+//! - Set the frequency and preload all channels before enabling the outputs. See: [`configure()`](Pca9685::configure).
+//! - Set the PWM period in nanoseconds. See: [`set_period_ns()`](Pca9685::set_period_ns).
 //! - Set the prescale value. See: [`set_prescale()`](Pca9685::set_prescale).
+//! - Read the prescale value and check whether it is the power-on value. See: [`get_prescale()`](Pca9685::get_prescale).
+//! - Set the frequency and verify that it was applied. See: [`set_frequency_verified()`](Pca9685::set_frequency_verified).
 //! - Select the output logic state direct or inverted. See: [`set_output_logic_state()`](Pca9685::set_output_logic_state).
 //! - Set when the outputs change. See: [`set_output_change_behavior()`](Pca9685::set_output_change_behavior).
+//! - Run writes with the outputs changing on the I2C STOP condition. See: [`with_synchronized_output()`](Pca9685::with_synchronized_output).
 //! - Set the output driver configuration. See: [`set_output_driver()`](Pca9685::set_output_driver).
 //! - Set the output value when outputs are disabled. See: [`set_disabled_output_value()`](Pca9685::set_disabled_output_value)
+//! - Set the complete output configuration at once. See: [`set_output_config()`](Pca9685::set_output_config).
+//! - Read the complete output configuration at once. See: [`get_output_config()`](Pca9685::get_output_config).
+//! - Check whether the outputs are configured as open-drain. See: [`is_open_drain()`](Pca9685::is_open_drain).
+//! - Describe the device configuration in one line (requires the `heapless` feature). See: [`describe_config()`](Pca9685::describe_config).
+//! - Correct the assumed frequency of the internal oscillator. See: [`set_oscillator_frequency()`](Pca9685::set_oscillator_frequency).
 //! - Select the EXTCLK pin as clock source. See: [`use_external_clock()`](Pca9685::use_external_clock).
+//! - Check whether the EXTCLK pin is used as clock source. See: [`is_external_clock()`](Pca9685::is_external_clock).
 //! - Enable/disable a programmable address. See: [`enable_programmable_address()`](Pca9685::enable_programmable_address).
 //! - Set a programmable address. See: [`set_programmable_address()`](Pca9685::set_programmable_address).
+//! - Read a programmable address back. See: [`get_programmable_address()`](Pca9685::get_programmable_address).
+//! - Read the subaddresses and whether they are enabled. See: [`subaddress_config()`](Pca9685::subaddress_config).
+//! - Check whether the device responds to the LED all call address. See: [`is_all_call_enabled()`](Pca9685::is_all_call_enabled).
+//! - Check the addressing configuration for suspicious settings. See: [`check_addressing()`](Pca9685::check_addressing).
+//! - Set and enable all subaddresses at once. See: [`configure_subaddresses()`](Pca9685::configure_subaddresses).
+//! - Program a new address and switch the driver to it in one step. See: [`reprogram_and_retarget()`](Pca9685::reprogram_and_retarget).
 //! - Change the address used by the driver. See: [`set_address()`](Pca9685::set_address).
 //! - Restart keeping the PWM register contents. See: [`enable_restart_and_disable()`](Pca9685::enable_restart_and_disable).
+//! - Set the _on_ and _off_ counters of a range of channels at once. See: [`write_channels_from()`](Pca9685::write_channels_from).
+//! - Set the same _off_ counter on a range of channels at once. See: [`set_channel_off_range()`](Pca9685::set_channel_off_range).
+//! - Write several channel changes in a single transfer so that they take effect at once. See: [`transaction()`](Pca9685::transaction).
+//! - Write a complete frame of channel values on the fast path. See: [`push_frame()`](Pca9685::push_frame).
+//! - Write a frame and verify it by reading it back. See: [`push_frame_verified()`](Pca9685::push_frame_verified).
+//! - Get the number of bytes written by frame and channel writes for bandwidth budgeting. See: [`frame_bytes()`](Pca9685::frame_bytes).
+//! - Write all channels in several non-blocking steps. See: [`FrameWriter`].
+//! - Fade smoothly between two frames of channel values. See: [`FrameFade`].
+//! - Read the _on_ and _off_ counters of all channels at once. See: [`get_all_channels_on_off_with_flags()`](Pca9685::get_all_channels_on_off_with_flags).
+//! - Decode the pulse of a channel from raw register values. See: [`pulse_from_raw()`].
+//! - Read the _on_ or _off_ counters of all channels at once. See: [`get_all_channels_on()`](Pca9685::get_all_channels_on).
+//! - Read the duty cycles of all channels at once. See: [`get_all_duty_cycles()`](Pca9685::get_all_duty_cycles).
+//! - Read and decode the registers of a channel. See: [`get_channel_registers()`](Pca9685::get_channel_registers).
+//! - Read the decoded counters and flags of a channel. See: [`get_channel_raw()`](Pca9685::get_channel_raw).
+//! - Find out which channels differ between two frames. See: [`diff_frames()`].
+//! - Count the channels switching on at the same tick to check the staggering. See: [`count_simultaneous_edges()`](Pca9685::count_simultaneous_edges).
+//! - Read the raw bytes of all channel registers. See: [`dump_channel_registers()`](Pca9685::dump_channel_registers).
+//! - Read the _on_ and _off_ counters of a range of channels into a buffer. See: [`read_channels_into()`](Pca9685::read_channels_into).
+//! - Compute the achievable frequency error. See: [`frequency_error()`](Pca9685::frequency_error).
+//! - Arm the restart logic without putting the device to sleep. See: [`enable_restart()`](Pca9685::enable_restart).
+//! - Restart the device if needed and re-apply the last pushed frame. See: [`service_watchdog()`](Pca9685::service_watchdog).
+//! - Initialize the device only if it is not configured yet. See: [`init_if_needed()`](Pca9685::init_if_needed).
+//! - Restore the power-on register values of this device only. See: [`reset_to_defaults()`](Pca9685::reset_to_defaults).
+//! - Light every output in sequence to check the wiring. See: [`self_test()`](Pca9685::self_test).
+//! - Get a handle to a single channel. See: [`handle()`](Pca9685::handle).
+//! - Split the driver into 16 embedded-hal `PwmPin` channels sharing the bus. See: [`split()`](Pca9685::split).
+//! - Get the pulse resolution at the current frequency. See: [`pulse_resolution_us()`](Pca9685::pulse_resolution_us).
+//! - Compute the lowest frequency achieving a pulse resolution. See: [`min_frequency_for_resolution_us()`].
+//! - Map the channel numbering of a board to the device channels. See: [`set_channel_mapping()`](Pca9685::set_channel_mapping).
+//! - Scan the bus for devices. See: [`scan_into()`](Pca9685::scan_into).
+//! - Reject channel writes while the device is asleep. See: [`set_strict_sleep()`](Pca9685::set_strict_sleep).
+//! - Skip writing unchanged _off_ counters. See: [`set_dedup()`](Pca9685::set_dedup).
+//! - Write any register directly for experimentation. See: [`write_raw_register()`](Pca9685::write_raw_register).
+//! - Convert a delay into an _on_ offset at the current frequency. See: [`phase_counts_for_us()`](Pca9685::phase_counts_for_us).
+//! - Parse a channel from its name like `"C5"` and format it back. See: [`Channel`].
+//! - Set the color of an RGBW LED fixture. See: [`RgbwFixture`].
+//! - Check whether a bus error occurred since the last check. See: [`bus_error_occurred()`](Pca9685::bus_error_occurred).
 //!
 //! [Introductory blog post](https://blog.eldruin.com/pca9685-pwm-led-servo-controller-driver-in-rust/)
 //!
@@ -153,21 +242,20 @@
 //! let mut pwm = Pca9685::new(dev, address).unwrap();
 //! pwm.enable().unwrap();
 //!
-//! // Turn channel 0 full on at 1024
-//! pwm.set_channel_full_on(Channel::C0, 1024).unwrap();
+//! // Turn channel 0 full on
+//! pwm.set_channel_full_on(Channel::C0, true).unwrap();
 //!
 //! // Turn channel 0 full off (full off takes precedence over on settings)
-//! pwm.set_channel_full_off(Channel::C0).unwrap();
+//! pwm.set_channel_full_off(Channel::C0, true).unwrap();
 //!
 //! // Return channel 0 to full on by deactivating full off.
-//! // The value is ignored because full on takes precedence
+//! // The off counter is ignored because full on takes precedence
 //! // over off settings except full off.
-//! let value_ignored_for_now = 2048;
-//! pwm.set_channel_off(Channel::C0, value_ignored_for_now).unwrap();
+//! pwm.set_channel_full_off(Channel::C0, false).unwrap();
 //!
 //! // Deactivate full on and set a duty cycle of 50% for channel 0.
 //! // (on from 0 to 2047, then off)
-//! pwm.set_channel_on(Channel::C0, 0).unwrap();
+//! pwm.set_channel_on_off(Channel::C0, 0, 2047).unwrap();
 //! ```
 //!
 //! ### Set a 50% duty cycle for all channels at once
@@ -186,6 +274,24 @@
 //! pwm.set_all_on_off(&on, &off);
 //! ```
 //!
+//! ### Move a servo
+//!
+//! ```no_run
+//! use linux_embedded_hal::I2cdev;
+//! use pwm_pca9685::{Address, Channel, Pca9685, ServoCalibration};
+//!
+//! let dev = I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+//! pwm.set_frequency(50.0).unwrap();
+//! pwm.enable().unwrap();
+//!
+//! // 0.5 ms to 2.5 ms over 180 degrees
+//! let cal = ServoCalibration::new(500.0, 2500.0, 180.0);
+//! pwm.set_servo_angle(Channel::C0, 90.0, cal).unwrap();
+//! // The same position as a byte
+//! pwm.set_servo_position_byte(Channel::C1, 128, cal).unwrap();
+//! ```
+//!
 //! ### Use a programmable address
 //!
 //! Several additional addresses can be programmed for the device (they are
@@ -247,11 +353,34 @@ use embedded_hal as hal;
 mod config;
 mod register_access;
 use crate::register_access::Register;
+mod brightness;
+pub use crate::brightness::build_gamma_lut;
 mod channels;
+pub use crate::channels::pulse_from_raw;
 mod device_impl;
+mod fade;
+pub use crate::fade::FrameFade;
+mod fixture;
+pub use crate::fixture::RgbwFixture;
+mod frame_writer;
+pub use crate::frame_writer::{diff_frames, FrameWriter};
+mod frequency;
+pub use crate::frequency::min_frequency_for_resolution_us;
+mod handle;
+pub use crate::handle::ChannelHandle;
+mod math;
+mod pwm_pin;
+pub use crate::pwm_pin::PwmChannel;
+mod ramp;
+pub use crate::ramp::{Ramp, RampShape};
+mod servo;
+pub use crate::servo::{ServoBank, ServoCalibration};
+mod transaction;
+pub use crate::transaction::Transaction;
 mod types;
 pub use crate::types::{
-    Address, Channel, DisabledOutputValue, Error, OutputDriver, OutputLogicState,
-    OutputStateChange, Pca9685, ProgrammableAddress,
+    Address, AddressingIssue, Channel, ChannelRegisters, DisabledOutputValue, Error, FlagState,
+    OutputConfig, OutputDriver, OutputLogicState, OutputStateChange, ParseChannelError, Pca9685,
+    ProgrammableAddress, PulseResult, RawCounter, MAX_DUTY, RESOLUTION_BITS,
 };
 pub use nb;
@@ -0,0 +1,88 @@
+//! Floating point functions which are not available in `core`.
+
+use core::f32::consts::LN_2;
+
+/// Compute `x` raised to the power of `y` for `x >= 0`.
+///
+/// This is accurate to about 1e-6 relative error, which is plenty for
+/// computing 12-bit counter values.
+pub(crate) fn powf(x: f32, y: f32) -> f32 {
+    if x == 1.0 || y == 0.0 {
+        1.0
+    } else if x <= 0.0 {
+        0.0
+    } else {
+        exp2(y * log2(x))
+    }
+}
+
+/// Binary logarithm of a positive, normal `x`.
+fn log2(x: f32) -> f32 {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127;
+    // mantissa in the range [1.0-2.0)
+    let m = f32::from_bits((bits & 0x007F_FFFF) | 0x3F80_0000);
+    // ln(m) = 2 * atanh(t) with t in the range [0-1/3)
+    let t = (m - 1.0) / (m + 1.0);
+    let t2 = t * t;
+    let ln_m = 2.0 * t * (1.0 + t2 * (1.0 / 3.0 + t2 * (1.0 / 5.0 + t2 * (1.0 / 7.0 + t2 / 9.0))));
+    exponent as f32 + ln_m / LN_2
+}
+
+/// Compute 2 raised to the power of `x`.
+fn exp2(x: f32) -> f32 {
+    if x < -126.0 {
+        return 0.0;
+    }
+    if x > 127.0 {
+        return core::f32::INFINITY;
+    }
+    let mut integer = x as i32;
+    if integer as f32 > x {
+        integer -= 1;
+    }
+    // e^(f * ln 2) with f in the range [0-1)
+    let z = (x - integer as f32) * LN_2;
+    let mut term = 1.0;
+    let mut fraction = 1.0;
+    for n in 1..=8 {
+        term *= z / n as f32;
+        fraction += term;
+    }
+    f32::from_bits(((integer + 127) as u32) << 23) * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f32, actual: f32) {
+        assert!(
+            (expected - actual).abs() <= expected.abs() * 1e-5,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn powf_special_values() {
+        assert_eq!(1.0, powf(1.0, 2.2));
+        assert_eq!(1.0, powf(0.5, 0.0));
+        assert_eq!(0.0, powf(0.0, 2.2));
+    }
+
+    #[test]
+    fn powf_integer_exponents() {
+        assert_close(0.25, powf(0.5, 2.0));
+        assert_close(8.0, powf(2.0, 3.0));
+        assert_close(0.001, powf(0.1, 3.0));
+    }
+
+    #[test]
+    fn powf_fractional_exponents() {
+        assert_close(0.217_637_64, powf(0.5, 2.2));
+        assert_close(0.707_106_77, powf(0.5, 0.5));
+        assert_close(0.000_005_077_05, powf(1.0 / 255.0, 2.2));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{hal, Channel, Pca9685};
+use core::cell::RefCell;
+use core::convert::TryFrom;
+
+/// Single channel of a PCA9685 shared through a `RefCell`, implementing
+/// the embedded-hal `PwmPin` trait.
+///
+/// Obtain all 16 channels with [`Pca9685::split()`].
+///
+/// The duty is the pulse length in ticks in the range `[0-4096]` as in
+/// [`Pca9685::set_channel_simple()`]. Values above 4096 are clamped.
+/// `disable()` sets the `full OFF` flag and `enable()` writes the last duty
+/// again. While disabled, `set_duty()` only stores the value.
+///
+/// Note that all channels share the PWM period set with
+/// [`Pca9685::set_frequency()`]. Changing it affects every pin and there
+/// is no way to set a period per pin.
+///
+/// The `PwmPin` methods cannot return errors. The driver only remembers
+/// that a bus error occurred, which can be checked with
+/// [`Pca9685::bus_error_occurred()`]. The error itself is lost.
+///
+/// Each method borrows the driver mutably for the duration of the write,
+/// so calling a pin method while the driver is borrowed elsewhere panics.
+#[derive(Debug)]
+pub struct PwmChannel<'a, I2C> {
+    pwm: &'a RefCell<Pca9685<I2C>>,
+    channel: Channel,
+    duty: u16,
+    enabled: bool,
+}
+
+impl<I2C, E> Pca9685<I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    /// Split the driver into 16 channels implementing `PwmPin`.
+    ///
+    /// Nothing is written to the device. The pins start enabled with a
+    /// stored duty of 0, independently of the current register contents.
+    /// See [`PwmChannel`] for the shared-period caveat.
+    pub fn split(pwm: &RefCell<Self>) -> [PwmChannel<'_, I2C>; 16] {
+        let pin = |index: u8| PwmChannel {
+            pwm,
+            channel: Channel::try_from(index).unwrap_or(Channel::C0),
+            duty: 0,
+            enabled: true,
+        };
+        [
+            pin(0),
+            pin(1),
+            pin(2),
+            pin(3),
+            pin(4),
+            pin(5),
+            pin(6),
+            pin(7),
+            pin(8),
+            pin(9),
+            pin(10),
+            pin(11),
+            pin(12),
+            pin(13),
+            pin(14),
+            pin(15),
+        ]
+    }
+}
+
+impl<'a, I2C> PwmChannel<'a, I2C> {
+    /// Get the channel of this pin.
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+impl<'a, I2C, E> hal::PwmPin for PwmChannel<'a, I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    type Duty = u16;
+
+    fn disable(&mut self) {
+        self.enabled = false;
+        let _ = self
+            .pwm
+            .borrow_mut()
+            .set_channel_full_off(self.channel, true);
+    }
+
+    fn enable(&mut self) {
+        self.enabled = true;
+        let _ = self
+            .pwm
+            .borrow_mut()
+            .set_channel_simple(self.channel, self.duty);
+    }
+
+    fn get_duty(&self) -> u16 {
+        self.duty
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        4096
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        self.duty = duty.min(4096);
+        if self.enabled {
+            let _ = self
+                .pwm
+                .borrow_mut()
+                .set_channel_simple(self.channel, self.duty);
+        }
+    }
+}
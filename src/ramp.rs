@@ -0,0 +1,100 @@
+use crate::{hal, Channel, Error, Pca9685};
+
+/// Shape of the waveform produced by a [`Ramp`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RampShape {
+    /// Rise from the minimum to the maximum, then fall back to the minimum.
+    Triangle,
+    /// Rise from the minimum to the maximum, then start again at the minimum.
+    Sawtooth,
+}
+
+/// Steps the pulse length of a channel between two values.
+///
+/// Every call to `step()` writes the current pulse length with
+/// [`Pca9685::set_channel_simple()`] and then advances it by the step size.
+/// The bounds are always reached exactly, then the direction is reversed
+/// (triangle) or the ramp starts again at the minimum (sawtooth).
+///
+/// The ramp does not keep track of time. How fast it runs only depends on
+/// how often `step()` is called, for example from a timer. This is useful
+/// for breathing LED effects.
+///
+/// ```no_run
+/// use linux_embedded_hal::I2cdev;
+/// use pwm_pca9685::{Address, Channel, Pca9685, Ramp, RampShape};
+///
+/// let dev = I2cdev::new("/dev/i2c-1").unwrap();
+/// let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+/// pwm.enable().unwrap();
+/// let mut ramp = Ramp::new(0, 4096, 64, RampShape::Triangle);
+/// loop {
+///     ramp.step(&mut pwm, Channel::C0).unwrap();
+///     // wait for the next tick
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ramp {
+    min: u16,
+    max: u16,
+    step: u16,
+    shape: RampShape,
+    value: u16,
+    rising: bool,
+}
+
+impl Ramp {
+    /// Create a ramp between the pulse lengths `min` and `max` in the range
+    /// `[0-4096]` starting at `min`.
+    ///
+    /// A `max` greater than 4096 is treated as 4096 and a `min` greater
+    /// than `max` as `max`. A `step` of 0 is treated as 1.
+    pub fn new(min: u16, max: u16, step: u16, shape: RampShape) -> Self {
+        let max = max.min(4096);
+        let min = min.min(max);
+        Ramp {
+            min,
+            max,
+            step: step.max(1),
+            shape,
+            value: min,
+            rising: true,
+        }
+    }
+
+    /// Get the pulse length written by the next call to `step()`.
+    pub fn value(&self) -> u16 {
+        self.value
+    }
+
+    /// Write the current pulse length to the selected channel and advance.
+    ///
+    /// If writing fails, the error is returned and the same pulse length is
+    /// written again on the next call.
+    pub fn step<I2C, E>(&mut self, pwm: &mut Pca9685<I2C>, channel: Channel) -> Result<(), Error<E>>
+    where
+        I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+    {
+        pwm.set_channel_simple(channel, self.value)?;
+        self.advance();
+        Ok(())
+    }
+
+    fn advance(&mut self) {
+        if self.rising {
+            if self.value < self.max {
+                self.value = self.value.saturating_add(self.step).min(self.max);
+            } else if self.shape == RampShape::Triangle {
+                self.rising = false;
+                self.value = self.value.saturating_sub(self.step).max(self.min);
+            } else {
+                self.value = self.min;
+            }
+        } else if self.value > self.min {
+            self.value = self.value.saturating_sub(self.step).max(self.min);
+        } else {
+            self.rising = true;
+            self.value = self.value.saturating_add(self.step).min(self.max);
+        }
+    }
+}
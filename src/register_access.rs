@@ -13,40 +13,44 @@ impl Register {
     pub const SUBADDR3: u8 = 0x04;
     pub const ALL_CALL_ADDR: u8 = 0x05;
     pub const C0_ON_L: u8 = 0x06;
-    pub const C0_OFF_L: u8 = 0x08;
-    pub const C1_ON_L: u8 = 0x0A;
-    pub const C1_OFF_L: u8 = 0x0C;
-    pub const C2_ON_L: u8 = 0x0E;
-    pub const C2_OFF_L: u8 = 0x10;
-    pub const C3_ON_L: u8 = 0x12;
-    pub const C3_OFF_L: u8 = 0x14;
-    pub const C4_ON_L: u8 = 0x16;
-    pub const C4_OFF_L: u8 = 0x18;
-    pub const C5_ON_L: u8 = 0x1A;
-    pub const C5_OFF_L: u8 = 0x1C;
-    pub const C6_ON_L: u8 = 0x1E;
-    pub const C6_OFF_L: u8 = 0x20;
-    pub const C7_ON_L: u8 = 0x22;
-    pub const C7_OFF_L: u8 = 0x24;
-    pub const C8_ON_L: u8 = 0x26;
-    pub const C8_OFF_L: u8 = 0x28;
-    pub const C9_ON_L: u8 = 0x2A;
-    pub const C9_OFF_L: u8 = 0x2C;
-    pub const C10_ON_L: u8 = 0x2E;
-    pub const C10_OFF_L: u8 = 0x30;
-    pub const C11_ON_L: u8 = 0x32;
-    pub const C11_OFF_L: u8 = 0x34;
-    pub const C12_ON_L: u8 = 0x36;
-    pub const C12_OFF_L: u8 = 0x38;
-    pub const C13_ON_L: u8 = 0x3A;
-    pub const C13_OFF_L: u8 = 0x3C;
-    pub const C14_ON_L: u8 = 0x3E;
-    pub const C14_OFF_L: u8 = 0x40;
-    pub const C15_ON_L: u8 = 0x42;
-    pub const C15_OFF_L: u8 = 0x44;
+    // The registers of the other channels follow, see `channel_on_register()`.
     pub const ALL_C_ON_L: u8 = 0xFA;
     pub const ALL_C_OFF_L: u8 = 0xFC;
     pub const PRE_SCALE: u8 = 0xFE;
+    pub const TEST_MODE: u8 = 0xFF;
+}
+
+/// Number of PWM channels of the device.
+///
+/// The channel registers are laid out as `CHANNEL_COUNT` consecutive blocks
+/// of `CHANNEL_REGISTERS` registers starting at `Register::C0_ON_L`. Code
+/// handling all channels relies only on these constants so that it can be
+/// reused for devices with a different number of channels.
+pub(crate) const CHANNEL_COUNT: usize = 16;
+/// Number of registers of each channel: `ON_L`, `ON_H`, `OFF_L` and `OFF_H`.
+pub(crate) const CHANNEL_REGISTERS: usize = 4;
+/// Number of registers of all channels.
+pub(crate) const CHANNEL_BLOCK_LEN: usize = CHANNEL_COUNT * CHANNEL_REGISTERS;
+
+/// Check whether writing `count` registers starting at `first` writes the
+/// registers of any channel, including the `ALL_LED` registers.
+fn writes_channels(first: u8, count: usize) -> bool {
+    let first = usize::from(first);
+    let last = first + count;
+    let channels =
+        usize::from(Register::C0_ON_L)..usize::from(Register::C0_ON_L) + CHANNEL_BLOCK_LEN;
+    let all = usize::from(Register::ALL_C_ON_L)..usize::from(Register::ALL_C_OFF_L) + 2;
+    (first < channels.end && last > channels.start) || (first < all.end && last > all.start)
+}
+
+/// Get the address of the `ON_L` register of the device channel `index`.
+pub(crate) fn channel_on_register(index: usize) -> u8 {
+    Register::C0_ON_L + (index * CHANNEL_REGISTERS) as u8
+}
+
+/// Get the address of the `OFF_L` register of the device channel `index`.
+pub(crate) fn channel_off_register(index: usize) -> u8 {
+    channel_on_register(index) + 2
 }
 
 impl<I2C, E> Pca9685<I2C>
@@ -54,21 +58,23 @@ where
     I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
 {
     pub(crate) fn write_mode2(&mut self, config: Config) -> Result<(), Error<E>> {
-        self.i2c
-            .write(self.address, &[Register::MODE2, config.mode2])
-            .map_err(Error::I2C)?;
+        self.write_registers(&[Register::MODE2, config.mode2])?;
         self.config.mode2 = config.mode2;
         Ok(())
     }
 
     pub(crate) fn write_mode1(&mut self, config: Config) -> Result<(), Error<E>> {
-        self.i2c
-            .write(self.address, &[Register::MODE1, config.mode1])
-            .map_err(Error::I2C)?;
+        self.write_registers(&[Register::MODE1, config.mode1])?;
         self.config.mode1 = config.mode1;
         Ok(())
     }
 
+    /// Enable auto-increment unless the cached `MODE1` value shows that it
+    /// is already enabled.
+    ///
+    /// The `MODE1` register is only written the first time, so the bulk
+    /// methods do not cause additional transfers in frequent updates. Any
+    /// reset through the driver resets the cached value as well.
     pub(crate) fn enable_auto_increment(&mut self) -> Result<(), Error<E>> {
         if self.config.is_low(BitFlagMode1::AutoInc) {
             let config = self.config;
@@ -78,7 +84,10 @@ where
         }
     }
 
-    #[allow(dead_code)]
+    // The device stores the low byte of a counter first. All counters are
+    // split into bytes with shifts so that this does not depend on the
+    // endianness of the target. There is no path reinterpreting memory
+    // (`unsafe` code is denied), so big-endian targets need no special care.
     pub(crate) fn write_two_double_registers(
         &mut self,
         address: u8,
@@ -86,18 +95,9 @@ where
         value1: u16,
     ) -> Result<(), Error<E>> {
         self.enable_auto_increment()?;
-        self.i2c
-            .write(
-                self.address,
-                &[
-                    address,
-                    value0 as u8,
-                    (value0 >> 8) as u8,
-                    value1 as u8,
-                    (value1 >> 8) as u8,
-                ],
-            )
-            .map_err(Error::I2C)
+        let [low0, high0] = counter_bytes(value0);
+        let [low1, high1] = counter_bytes(value1);
+        self.write_registers(&[address, low0, high0, low1, high1])
     }
 
     pub(crate) fn write_double_register(
@@ -106,16 +106,143 @@ where
         value: u16,
     ) -> Result<(), Error<E>> {
         self.enable_auto_increment()?;
+        let [low, high] = counter_bytes(value);
+        self.write_registers(&[address, low, high])
+    }
+
+    /// Write `data[1..]` to the registers starting at `data[0]`.
+    ///
+    /// Writing several registers requires auto-increment to be enabled.
+    ///
+    /// Returns `Error::DeviceAsleep` without writing anything if channel
+    /// registers would be written while the device is asleep and this is
+    /// rejected with `set_strict_sleep()`.
+    pub(crate) fn write_registers(&mut self, data: &[u8]) -> Result<(), Error<E>> {
+        if self.strict_sleep
+            && self.config.is_high(BitFlagMode1::Sleep)
+            && writes_channels(data[0], data.len() - 1)
+        {
+            return Err(Error::DeviceAsleep);
+        }
+        // Forget before writing so that the cache is also invalid if the write fails.
+        self.forget_last_off(data[0], data.len() - 1);
+        self.trace("write", data[0], &data[1..]);
         self.i2c
-            .write(self.address, &[address, value as u8, (value >> 8) as u8])
-            .map_err(Error::I2C)
+            .write(self.address, data)
+            .map_err(|e| self.latch_error(e))
+    }
+
+    /// Forget the last `OFF` value written by `set_channel_off()` for
+    /// every channel whose `OFF` counter is within the written registers.
+    /// The last frame written by `push_frame()` is forgotten if any channel
+    /// register is written.
+    fn forget_last_off(&mut self, first: u8, count: usize) {
+        let first = usize::from(first);
+        let last = first + count;
+        if first < usize::from(channel_on_register(CHANNEL_COUNT))
+            && last > usize::from(Register::C0_ON_L)
+        {
+            self.last_frame = None;
+        }
+        if first < usize::from(Register::ALL_C_OFF_L) + 2
+            && last > usize::from(Register::ALL_C_ON_L)
+        {
+            self.last_frame = None;
+            self.last_off = [None; CHANNEL_COUNT];
+            return;
+        }
+        for (i, value) in self.last_off.iter_mut().enumerate() {
+            let off_l = usize::from(channel_off_register(i));
+            if first <= off_l + 1 && off_l < last {
+                *value = None;
+            }
+        }
+    }
+
+    /// Read consecutive registers starting at `first` into `data`.
+    ///
+    /// This enables auto-increment if necessary.
+    pub(crate) fn read_registers(&mut self, first: u8, data: &mut [u8]) -> Result<(), Error<E>> {
+        self.enable_auto_increment()?;
+        self.i2c
+            .write_read(self.address, &[first], data)
+            .map_err(|e| self.latch_error(e))?;
+        self.trace("read", first, data);
+        Ok(())
     }
 
     pub(crate) fn read_register(&mut self, address: u8) -> Result<u8, Error<E>> {
         let mut data = [0];
         self.i2c
             .write_read(self.address, &[address], &mut data)
-            .map_err(Error::I2C)
-            .and(Ok(data[0]))
+            .map_err(|e| self.latch_error(e))?;
+        self.trace("read", address, &data);
+        Ok(data[0])
+    }
+
+    /// Log a register access with the `log` feature.
+    #[cfg_attr(not(feature = "log"), allow(unused_variables))]
+    fn trace(&self, access: &str, first: u8, data: &[u8]) {
+        #[cfg(feature = "log")]
+        log::trace!(
+            "PCA9685 {:#04x}: {} register {:#04x}: {:02x?}",
+            self.address,
+            access,
+            first,
+            data
+        );
+    }
+
+    /// Remember that a bus error occurred for `bus_error_occurred()`.
+    fn latch_error(&mut self, error: E) -> Error<E> {
+        self.bus_error = true;
+        Error::I2C(error)
+    }
+}
+
+/// Split a register value consisting of the 12-bit counter and the
+/// `full ON/OFF` flag in bit 12 into the low and high register bytes.
+///
+/// The reserved bits 13-15 are always written as 0.
+pub(crate) fn counter_bytes(value: u16) -> [u8; 2] {
+    let value = value & 0x1FFF;
+    [value as u8, (value >> 8) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_registers_layout() {
+        assert_eq!(Register::C0_ON_L, channel_on_register(0));
+        assert_eq!(0x08, channel_off_register(0));
+        assert_eq!(0x42, channel_on_register(CHANNEL_COUNT - 1));
+        assert_eq!(0x44, channel_off_register(CHANNEL_COUNT - 1));
+        assert_eq!(0x46, usize::from(Register::C0_ON_L) + CHANNEL_BLOCK_LEN);
+    }
+
+    #[test]
+    fn detect_channel_writes() {
+        assert!(!writes_channels(Register::MODE1, 6));
+        assert!(writes_channels(Register::MODE1, 7));
+        assert!(writes_channels(0x45, 1));
+        assert!(!writes_channels(0x46, 1));
+        assert!(!writes_channels(0xF9, 1));
+        assert!(writes_channels(0xF9, 2));
+        assert!(writes_channels(Register::ALL_C_OFF_L + 1, 1));
+        assert!(!writes_channels(Register::PRE_SCALE, 1));
+    }
+
+    #[test]
+    fn counter_bytes_keep_counter_and_flag() {
+        assert_eq!([0x34, 0x02], counter_bytes(0x0234));
+        assert_eq!([0xFF, 0x1F], counter_bytes(0x1FFF));
+    }
+
+    #[test]
+    fn counter_bytes_clear_reserved_bits() {
+        assert_eq!([0x34, 0x12], counter_bytes(0xF234));
+        assert_eq!([0, 0], counter_bytes(0xE000));
     }
 }
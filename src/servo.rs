@@ -0,0 +1,247 @@
+use crate::{hal, Channel, Error, Pca9685};
+
+/// Pulse width range of a servo.
+///
+/// Hobby servos usually expect pulses of 1 ms to 2 ms repeated at about
+/// 50 Hz, but the exact range and the angle it corresponds to vary between
+/// models. The default calibration is 1000 µs to 2000 µs over 180 degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoCalibration {
+    /// Pulse width in microseconds at 0 degrees.
+    pub min_us: f32,
+    /// Pulse width in microseconds at `range_deg` degrees.
+    pub max_us: f32,
+    /// Angle range of the servo in degrees.
+    pub range_deg: f32,
+}
+
+impl ServoCalibration {
+    /// Create a new calibration.
+    pub fn new(min_us: f32, max_us: f32, range_deg: f32) -> Self {
+        ServoCalibration {
+            min_us,
+            max_us,
+            range_deg,
+        }
+    }
+
+    /// Pulse width in microseconds at `fraction` of the range.
+    fn pulse_us(&self, fraction: f32) -> f32 {
+        self.min_us + (self.max_us - self.min_us) * fraction
+    }
+}
+
+impl Default for ServoCalibration {
+    fn default() -> Self {
+        ServoCalibration::new(1000.0, 2000.0, 180.0)
+    }
+}
+
+/// Channels and calibrations of several servos, for example the joints
+/// of a robot arm.
+///
+/// Each of the up to 16 joints is identified by its index and mapped to a
+/// channel. All joints share the calibration of the bank unless a joint
+/// has its own calibration.
+///
+/// ```
+/// use pwm_pca9685::{Channel, ServoBank, ServoCalibration};
+///
+/// let bank = ServoBank::new(ServoCalibration::default())
+///     .with_joint(0, Channel::C4)
+///     .with_joint(1, Channel::C5)
+///     .with_joint_calibration(2, Channel::C6, ServoCalibration::new(500.0, 2500.0, 270.0));
+/// assert_eq!(Some(Channel::C6), bank.joint_channel(2));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoBank {
+    calibration: ServoCalibration,
+    joints: [Option<(Channel, Option<ServoCalibration>)>; 16],
+}
+
+impl ServoBank {
+    /// Create a bank without joints using `calibration` for all of them.
+    pub fn new(calibration: ServoCalibration) -> Self {
+        ServoBank {
+            calibration,
+            joints: [None; 16],
+        }
+    }
+
+    /// Map `joint` to `channel` using the calibration of the bank.
+    ///
+    /// An existing mapping of the joint is replaced. Joints outside the
+    /// range `[0-15]` are ignored.
+    pub fn with_joint(mut self, joint: usize, channel: Channel) -> Self {
+        if let Some(entry) = self.joints.get_mut(joint) {
+            *entry = Some((channel, None));
+        }
+        self
+    }
+
+    /// Map `joint` to `channel` using its own calibration.
+    ///
+    /// This behaves like `with_joint()` otherwise.
+    pub fn with_joint_calibration(
+        mut self,
+        joint: usize,
+        channel: Channel,
+        calibration: ServoCalibration,
+    ) -> Self {
+        if let Some(entry) = self.joints.get_mut(joint) {
+            *entry = Some((channel, Some(calibration)));
+        }
+        self
+    }
+
+    /// Get the channel of `joint`, if it is mapped.
+    pub fn joint_channel(&self, joint: usize) -> Option<Channel> {
+        self.joint(joint).map(|(channel, _)| channel)
+    }
+
+    /// Move the servo of `joint` to an angle in degrees.
+    ///
+    /// This uses `set_servo_angle()` with the channel and calibration of the
+    /// joint. Returns `Error::InvalidInputData` if the joint is not mapped.
+    pub fn set_angle<I2C, E>(
+        &self,
+        driver: &mut Pca9685<I2C>,
+        joint: usize,
+        angle_deg: f32,
+    ) -> Result<(), Error<E>>
+    where
+        I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+    {
+        let (channel, calibration) = self.joint(joint).ok_or(Error::InvalidInputData)?;
+        driver.set_servo_angle(channel, angle_deg, calibration)
+    }
+
+    /// Channel and effective calibration of `joint`.
+    fn joint(&self, joint: usize) -> Option<(Channel, ServoCalibration)> {
+        let (channel, calibration) = (*self.joints.get(joint)?)?;
+        Some((channel, calibration.unwrap_or(self.calibration)))
+    }
+}
+
+impl<I2C, E> Pca9685<I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    /// Set the pulse width of the selected channel in microseconds.
+    ///
+    /// The width is converted into counter ticks at the current frequency
+    /// (see `pulse_resolution_us()`) and rounded to the nearest tick. The
+    /// pulse starts at the `ON` offset of the channel, like with
+    /// `set_channel_duty_cycle()`.
+    ///
+    /// Returns `Error::InvalidInputData` if the width is negative or longer
+    /// than the period.
+    pub fn set_channel_pulse_us(
+        &mut self,
+        channel: Channel,
+        width_us: f32,
+    ) -> Result<(), Error<E>> {
+        if width_us.is_nan() || width_us < 0.0 {
+            return Err(Error::InvalidInputData);
+        }
+        // `round()` is not available in `core`. The value is always positive here.
+        let counts = width_us / self.pulse_resolution_us() + 0.5;
+        if counts > 4096.0 {
+            return Err(Error::InvalidInputData);
+        }
+        self.write_channel_pulse(channel, counts as u16)
+    }
+
+    /// Set both edges of the pulse of the selected channel in microseconds.
+    ///
+    /// The pulse starts `start_us` after the beginning of the period and is
+    /// high for `width_us`, for example 1 ms starting 200 µs into the
+    /// period. The start and the end of the pulse are converted into counter
+    /// ticks at the current frequency (see `pulse_resolution_us()`) and
+    /// rounded to the nearest tick, so the pulse never extends into the next
+    /// period. A pulse ending exactly at the end of the period wraps the `OFF`
+    /// counter around to 0. This is the timed counterpart of
+    /// `set_channel_window()`.
+    ///
+    /// Both `full ON` and `full OFF` flags are cleared and the `ON` and `OFF`
+    /// counters are written in a single transaction.
+    ///
+    /// Returns `Error::InvalidInputData` if any value is negative, if the
+    /// pulse does not fit into the period or if both edges end up at the
+    /// same counter value.
+    pub fn set_channel_pulse_us_window(
+        &mut self,
+        channel: Channel,
+        start_us: f32,
+        width_us: f32,
+    ) -> Result<(), Error<E>> {
+        let resolution_us = self.pulse_resolution_us();
+        if start_us.is_nan()
+            || width_us.is_nan()
+            || start_us < 0.0
+            || width_us < 0.0
+            || start_us + width_us > resolution_us * 4096.0
+        {
+            return Err(Error::InvalidInputData);
+        }
+        // `round()` is not available in `core`. The values are always positive here.
+        // Rounding the end instead of the width keeps it within the period.
+        let on = (start_us / resolution_us + 0.5) as u16 % 4096;
+        let end = ((start_us + width_us) / resolution_us + 0.5) as u16;
+        let off = end.min(4096) % 4096;
+        if on == off {
+            return Err(Error::InvalidInputData);
+        }
+        self.set_channel_on_off(channel, on, off)
+    }
+
+    /// Move the servo on the selected channel to an angle in degrees.
+    ///
+    /// The angle must be in the range `[0-cal.range_deg]` and is mapped
+    /// linearly onto the pulse widths of the calibration, which are then
+    /// set with `set_channel_pulse_us()`.
+    pub fn set_servo_angle(
+        &mut self,
+        channel: Channel,
+        angle_deg: f32,
+        cal: ServoCalibration,
+    ) -> Result<(), Error<E>> {
+        if angle_deg.is_nan()
+            || cal.range_deg <= 0.0
+            || angle_deg < 0.0
+            || angle_deg > cal.range_deg
+        {
+            return Err(Error::InvalidInputData);
+        }
+        self.set_channel_pulse_us(channel, cal.pulse_us(angle_deg / cal.range_deg))
+    }
+
+    /// Move the servo on the selected channel to a position given as a byte.
+    ///
+    /// The positions 0 to 255 are mapped linearly onto the pulse widths of
+    /// the calibration, which are then set with `set_channel_pulse_us()`.
+    /// This is useful for positions received over a byte-oriented protocol.
+    /// For a symmetric calibration 128 is the center position (within half
+    /// a step).
+    pub fn set_servo_position_byte(
+        &mut self,
+        channel: Channel,
+        position: u8,
+        cal: ServoCalibration,
+    ) -> Result<(), Error<E>> {
+        self.set_channel_pulse_us(channel, cal.pulse_us(f32::from(position) / 255.0))
+    }
+
+    /// Move the servo on the selected channel to its center position.
+    ///
+    /// This sets the pulse width halfway between `cal.min_us` and
+    /// `cal.max_us` with `set_channel_pulse_us()`. Bringing all servos to
+    /// the center before powering them avoids violent jerks at startup.
+    pub fn center_servo(
+        &mut self,
+        channel: Channel,
+        cal: ServoCalibration,
+    ) -> Result<(), Error<E>> {
+        self.set_channel_pulse_us(channel, cal.pulse_us(0.5))
+    }
+}
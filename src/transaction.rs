@@ -0,0 +1,158 @@
+use crate::{
+    hal,
+    register_access::{CHANNEL_BLOCK_LEN, CHANNEL_COUNT, CHANNEL_REGISTERS},
+    Channel, Error, Pca9685, Register,
+};
+
+/// Channel counter changes collected by [`Pca9685::transaction()`].
+///
+/// The methods only record the changes. Nothing is written to the device
+/// until the closure passed to `transaction()` returns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    on: [Option<u16>; CHANNEL_COUNT],
+    off: [Option<u16>; CHANNEL_COUNT],
+    keep_on_flag: [bool; CHANNEL_COUNT],
+    keep_off_flag: [bool; CHANNEL_COUNT],
+    invalid: bool,
+}
+
+impl Transaction {
+    fn new() -> Self {
+        Transaction {
+            on: [None; CHANNEL_COUNT],
+            off: [None; CHANNEL_COUNT],
+            keep_on_flag: [false; CHANNEL_COUNT],
+            keep_off_flag: [false; CHANNEL_COUNT],
+            invalid: false,
+        }
+    }
+
+    /// Set the `ON` counter of the selected channel.
+    ///
+    /// Like `Pca9685::set_channel_on()`, this does not touch the `full ON`
+    /// flag. Its current value is read from the device before writing.
+    pub fn set_channel_on(&mut self, channel: Channel, value: u16) {
+        self.record(channel, Some(value), None, true);
+    }
+
+    /// Set the `OFF` counter of the selected channel.
+    ///
+    /// Like `Pca9685::set_channel_off()`, this does not touch the `full OFF`
+    /// flag. Its current value is read from the device before writing.
+    pub fn set_channel_off(&mut self, channel: Channel, value: u16) {
+        self.record(channel, None, Some(value), true);
+    }
+
+    /// Set the `ON` and `OFF` counters of the selected channel.
+    ///
+    /// Like `Pca9685::set_channel_on_off()`, this clears the `full ON` and
+    /// `full OFF` flags.
+    pub fn set_channel_on_off(&mut self, channel: Channel, on: u16, off: u16) {
+        self.record(channel, Some(on), Some(off), false);
+    }
+
+    fn record(&mut self, channel: Channel, on: Option<u16>, off: Option<u16>, keep_flags: bool) {
+        let too_big = |value: Option<u16>| value.unwrap_or(0) > 4095;
+        if channel == Channel::All || too_big(on) || too_big(off) {
+            self.invalid = true;
+            return;
+        }
+        let index = channel as usize;
+        if on.is_some() {
+            self.on[index] = on;
+            self.keep_on_flag[index] = keep_flags;
+        }
+        if off.is_some() {
+            self.off[index] = off;
+            self.keep_off_flag[index] = keep_flags;
+        }
+    }
+}
+
+impl<I2C, E> Pca9685<I2C>
+where
+    I2C: hal::blocking::i2c::Write<Error = E> + hal::blocking::i2c::WriteRead<Error = E>,
+{
+    /// Collect several channel counter changes and write them in a single
+    /// I2C transfer.
+    ///
+    /// The closure records the changes in a [`Transaction`] and they are
+    /// written once it returns. Since all changes are written in one
+    /// transfer, they take effect at the same time: with the default output
+    /// change behavior all outputs change on the single I2C STOP condition
+    /// (see `set_output_change_behavior()`).
+    ///
+    /// The transfer spans the registers from the first to the last changed
+    /// one. If there are unchanged registers in between, or if a counter is
+    /// changed without its `full ON/OFF` flag, the current values are read
+    /// from the device beforehand so that they can be written back
+    /// unmodified.
+    ///
+    /// Returns `Error::InvalidInputData` without writing anything if any
+    /// change selected `Channel::All` or a counter value greater than 4095.
+    ///
+    /// ```no_run
+    /// use linux_embedded_hal::I2cdev;
+    /// use pwm_pca9685::{Address, Channel, Pca9685};
+    ///
+    /// let dev = I2cdev::new("/dev/i2c-1").unwrap();
+    /// let mut pwm = Pca9685::new(dev, Address::default()).unwrap();
+    /// pwm.transaction(|tx| {
+    ///     tx.set_channel_off(Channel::C0, 1024);
+    ///     tx.set_channel_on_off(Channel::C3, 512, 2048);
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn transaction<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut Transaction),
+    {
+        let mut tx = Transaction::new();
+        f(&mut tx);
+        if tx.invalid {
+            return Err(Error::InvalidInputData);
+        }
+        // `data[i + 1]` holds the value of the register `C0_ON_L + i`.
+        let mut data = [0; CHANNEL_BLOCK_LEN + 1];
+        let mut changed = [false; CHANNEL_BLOCK_LEN];
+        // `keep_flag[i]` is set for the high registers whose flag is kept.
+        let mut keep_flag = [false; CHANNEL_BLOCK_LEN];
+        for logical in 0..CHANNEL_COUNT {
+            let base = self.physical_index(logical) * CHANNEL_REGISTERS;
+            let values = [
+                (0, tx.on[logical], tx.keep_on_flag[logical]),
+                (2, tx.off[logical], tx.keep_off_flag[logical]),
+            ];
+            for (offset, value, keep) in values.iter() {
+                if let Some(value) = value {
+                    let i = base + offset;
+                    data[i + 1] = *value as u8;
+                    data[i + 2] = (*value >> 8) as u8;
+                    changed[i] = true;
+                    changed[i + 1] = true;
+                    keep_flag[i + 1] = *keep;
+                }
+            }
+        }
+        let first = match changed.iter().position(|c| *c) {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        let last = changed.iter().rposition(|c| *c).unwrap_or(first);
+        if changed[first..=last].iter().any(|c| !c) || keep_flag.iter().any(|k| *k) {
+            let mut current = [0; CHANNEL_BLOCK_LEN];
+            self.read_registers(Register::C0_ON_L + first as u8, &mut current[first..=last])?;
+            for i in first..=last {
+                if !changed[i] {
+                    data[i + 1] = current[i];
+                } else if keep_flag[i] {
+                    data[i + 1] |= current[i] & 0x10;
+                }
+            }
+        }
+        data[first] = Register::C0_ON_L + first as u8;
+        self.enable_auto_increment()?;
+        self.write_registers(&data[first..=last + 1])
+    }
+}
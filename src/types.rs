@@ -1,9 +1,24 @@
-use crate::config::Config;
+use crate::{
+    config::Config,
+    frequency::{frequency_for, INTERNAL_OSCILLATOR_HZ, PRESCALE_DEFAULT},
+    register_access::CHANNEL_COUNT,
+};
 use core::convert::TryFrom;
-const DEVICE_BASE_ADDRESS: u8 = 0b100_0000;
+use core::fmt;
+use core::str::FromStr;
+pub(crate) const DEVICE_BASE_ADDRESS: u8 = 0b100_0000;
+
+/// Resolution of the PWM counters in bits.
+pub const RESOLUTION_BITS: u8 = 12;
+
+/// Maximum value of the `ON` and `OFF` counters.
+pub const MAX_DUTY: u16 = (1 << RESOLUTION_BITS) - 1;
 
 /// PCA9685 PWM/Servo/LED controller.
-#[derive(Debug, Default)]
+///
+/// The `Debug` output shows the address and the configuration cached by
+/// the driver. It does not communicate with the device and does not require
+/// `I2C: Debug`.
 pub struct Pca9685<I2C> {
     /// The concrete I²C device implementation.
     pub(crate) i2c: I2C,
@@ -11,15 +26,156 @@ pub struct Pca9685<I2C> {
     pub(crate) address: u8,
     /// Current device configuration.
     pub(crate) config: Config,
+    /// Current prescale value.
+    pub(crate) prescale: u8,
+    /// Mapping from logical to device channels. `None` means identity.
+    pub(crate) channel_remap: Option<[u8; CHANNEL_COUNT]>,
+    /// `ON` offset applied by the duty cycle setters for each channel.
+    pub(crate) on_offsets: [u16; CHANNEL_COUNT],
+    /// Minimum pulse length enforced by the duty cycle setters for each channel.
+    pub(crate) min_on: [u16; CHANNEL_COUNT],
+    /// Whether to skip `set_channel_off()` writes of unchanged values.
+    pub(crate) dedup: bool,
+    /// Whether to reject channel writes while the device is asleep.
+    pub(crate) strict_sleep: bool,
+    /// Last value written by `set_channel_off()` for each device channel.
+    pub(crate) last_off: [Option<u16>; CHANNEL_COUNT],
+    /// Last frame written by `push_frame()` in device channel order.
+    pub(crate) last_frame: Option<[u16; 2 * CHANNEL_COUNT]>,
+    /// Whether to keep the last frame pushed for `service_watchdog()`.
+    pub(crate) retain_frame: bool,
+    /// Last frame given to `push_frame()` in logical channel order.
+    pub(crate) retained_frame: Option<[u16; 2 * CHANNEL_COUNT]>,
+    /// Assumed frequency of the clock source.
+    pub(crate) oscillator_hz: u32,
+    /// Assumed frequency of the internal oscillator.
+    pub(crate) internal_oscillator_hz: u32,
+    /// `ON` and `OFF` values of each muted channel before muting it.
+    pub(crate) muted: [Option<(u16, u16)>; CHANNEL_COUNT],
+    /// Whether a bus error occurred since the last `clear_error()`.
+    pub(crate) bus_error: bool,
+}
+
+impl<I2C> Pca9685<I2C> {
+    /// Create a driver instance with the power-on configuration.
+    pub(crate) fn with_defaults(i2c: I2C, address: u8) -> Self {
+        Pca9685 {
+            i2c,
+            address,
+            config: Config::default(),
+            prescale: PRESCALE_DEFAULT,
+            channel_remap: None,
+            on_offsets: [0; CHANNEL_COUNT],
+            min_on: [0; CHANNEL_COUNT],
+            dedup: false,
+            strict_sleep: false,
+            last_off: [None; CHANNEL_COUNT],
+            last_frame: None,
+            retain_frame: false,
+            retained_frame: None,
+            oscillator_hz: INTERNAL_OSCILLATOR_HZ,
+            internal_oscillator_hz: INTERNAL_OSCILLATOR_HZ,
+            muted: [None; CHANNEL_COUNT],
+            bus_error: false,
+        }
+    }
+}
+
+/// Creates an instance at the default address `0x40` with the power-on
+/// configuration like `Pca9685::new()` does.
+impl<I2C: Default> Default for Pca9685<I2C> {
+    fn default() -> Self {
+        Pca9685::with_defaults(I2C::default(), DEVICE_BASE_ADDRESS)
+    }
+}
+
+impl<I2C> fmt::Debug for Pca9685<I2C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pca9685")
+            .field("address", &format_args!("{:#04x}", self.address))
+            .field("mode1", &format_args!("{:#010b}", self.config.mode1))
+            .field("mode2", &format_args!("{:#010b}", self.config.mode2))
+            .field("prescale", &self.prescale)
+            .field(
+                "frequency_hz",
+                &frequency_for(self.oscillator_hz, self.prescale),
+            )
+            .field("oscillator_hz", &self.oscillator_hz)
+            .finish()
+    }
+}
+
+/// Well-known addresses.
+///
+/// These are defined for `Pca9685<()>` only so that they can be used as
+/// `Pca9685::DEFAULT_ADDRESS` without naming the type of the I²C bus.
+impl Pca9685<()> {
+    /// Address of a device with all address pins (A5-A0) connected to
+    /// ground: `0x40`.
+    ///
+    /// Each pin connected to VDD sets the corresponding address bit, so
+    /// the hardware address is in the range `0x40-0x7F`. See [`Address`].
+    pub const DEFAULT_ADDRESS: u8 = DEVICE_BASE_ADDRESS;
+
+    /// LED all call address at power-on, to which every device responds: `0x70`.
+    ///
+    /// No device should use this as hardware address unless the all call
+    /// address is disabled or changed.
+    pub const ALL_CALL_ADDRESS: u8 = 0x70;
 }
 
 /// All possible errors in this crate
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Error<E> {
     /// I²C bus error
+    ///
+    /// `embedded-hal` does not report how many bytes were transferred before
+    /// the error. When writing several registers at once, any of them may
+    /// have been updated, so the contents of the written registers are
+    /// unknown afterwards. Values cached by the driver for these registers
+    /// (see `set_dedup()`) are forgotten.
     I2C(E),
     /// Invalid input data provided
     InvalidInputData,
+    /// The register must not be written in normal operation
+    ForbiddenRegister,
+    /// The programmable address is the same as the address of the device
+    AddressConflict,
+    /// The value read back from the device does not match the written value
+    ///
+    /// This contains the first mismatched channel when verifying channel
+    /// values (see `push_frame_verified()`) and `None` otherwise.
+    VerificationFailed(Option<Channel>),
+    /// The addressing configuration of the device is suspicious
+    Addressing(AddressingIssue),
+    /// The channels were not written because the device is asleep
+    /// (see `set_strict_sleep()`)
+    DeviceAsleep,
+}
+
+impl<E> Error<E> {
+    /// Wrap an error of the I²C bus.
+    ///
+    /// A blanket `impl<E> From<E> for Error<E>` is not possible because it
+    /// would conflict with `impl<T> From<T> for T` from `core`. Instead, this
+    /// can be passed to `map_err()` to use `?` on the results of code talking
+    /// to the bus directly, for example in functions which also use the driver:
+    ///
+    /// ```
+    /// use embedded_hal::blocking::i2c::Write;
+    /// use pwm_pca9685::Error;
+    ///
+    /// fn reset_bus<I2C: Write>(i2c: &mut I2C) -> Result<(), Error<I2C::Error>> {
+    ///     // General call software reset
+    ///     i2c.write(0x00, &[0x06]).map_err(Error::from_i2c)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// This is the same as `Error::I2C(e)`, which can be used as well.
+    pub fn from_i2c(e: E) -> Self {
+        Error::I2C(e)
+    }
 }
 
 /// Output channel selection
@@ -90,6 +246,63 @@ impl_try_from_for_channel!(u8);
 impl_try_from_for_channel!(u16);
 impl_try_from_for_channel!(usize);
 
+impl fmt::Display for Channel {
+    /// Formats the channel as `C0` to `C15` or `All`, as accepted by `FromStr`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Channel::All => f.write_str("All"),
+            channel => write!(f, "C{}", *channel as u8),
+        }
+    }
+}
+
+/// Error returned when parsing a `Channel` from a string fails.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParseChannelError;
+
+impl fmt::Display for ParseChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid channel, expected C0 to C15 or All")
+    }
+}
+
+impl FromStr for Channel {
+    type Err = ParseChannelError;
+
+    /// Parses `C0` to `C15` and `All`. Leading zeros are not accepted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "All" {
+            return Ok(Channel::All);
+        }
+        if !s.starts_with('C') {
+            return Err(ParseChannelError);
+        }
+        let digits = &s[1..];
+        let valid = match digits.as_bytes() {
+            [d] => d.is_ascii_digit(),
+            [b'1', d] => d.is_ascii_digit(),
+            _ => false,
+        };
+        if !valid {
+            return Err(ParseChannelError);
+        }
+        digits
+            .parse::<u8>()
+            .ok()
+            .and_then(|index| Channel::try_from(index).ok())
+            .ok_or(ParseChannelError)
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Channel {
+    type Error = ParseChannelError;
+
+    /// Same as `FromStr`.
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
 /// Output logic state inversion
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OutputLogicState {
@@ -163,6 +376,122 @@ impl Default for DisabledOutputValue {
     }
 }
 
+/// Output configuration as stored in the `MODE2` register.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OutputConfig {
+    /// Output driver configuration.
+    pub driver: OutputDriver,
+    /// Output logic state inversion.
+    pub invert: OutputLogicState,
+    /// Output state change behavior.
+    pub change: OutputStateChange,
+    /// Value set to all outputs when the output drivers are disabled (`OE` = 1).
+    pub disabled_state: DisabledOutputValue,
+}
+
+/// Decoded `ON` or `OFF` value of a channel as returned by
+/// `Pca9685::get_channel_raw()`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RawCounter {
+    /// 12-bit counter value.
+    pub count: u16,
+    /// `full ON` flag for the `ON` value or `full OFF` flag for the `OFF` value.
+    pub full: bool,
+}
+
+impl RawCounter {
+    /// Decode a value containing the 12-bit counter plus the `full ON/OFF`
+    /// flag in bit 12. Any higher bits are ignored.
+    pub fn from_raw(value: u16) -> Self {
+        RawCounter {
+            count: value & 0x0FFF,
+            full: value & 0x1000 != 0,
+        }
+    }
+}
+
+/// Contents of the `ON_L`, `ON_H`, `OFF_L` and `OFF_H` registers of a channel.
+///
+/// The `ON` and `OFF` values consist of the 12-bit counter plus the
+/// `full ON/OFF` flag in bit 12, like the values used by
+/// `Pca9685::write_channels_from()`. The reserved bits 7:5 of the high
+/// registers are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChannelRegisters {
+    on: u16,
+    off: u16,
+}
+
+impl ChannelRegisters {
+    /// Create the register contents from `ON` and `OFF` values including
+    /// the `full ON/OFF` flag in bit 12. Any higher bits are ignored.
+    pub fn new(on: u16, off: u16) -> Self {
+        ChannelRegisters {
+            on: on & 0x1FFF,
+            off: off & 0x1FFF,
+        }
+    }
+
+    /// Create the register contents from the raw bytes in register order.
+    pub fn from_bytes(bytes: [u8; 4]) -> Self {
+        ChannelRegisters::new(
+            u16::from_le_bytes([bytes[0], bytes[1]]),
+            u16::from_le_bytes([bytes[2], bytes[3]]),
+        )
+    }
+
+    /// Get the raw bytes in register order.
+    pub fn to_bytes(self) -> [u8; 4] {
+        let [on_l, on_h] = self.on.to_le_bytes();
+        let [off_l, off_h] = self.off.to_le_bytes();
+        [on_l, on_h, off_l, off_h]
+    }
+
+    /// Get the 12-bit `ON` counter.
+    pub fn on(self) -> u16 {
+        self.on & 0x0FFF
+    }
+
+    /// Get the 12-bit `OFF` counter.
+    pub fn off(self) -> u16 {
+        self.off & 0x0FFF
+    }
+
+    /// Get the `ON` value including the `full ON` flag in bit 12.
+    pub fn on_with_flag(self) -> u16 {
+        self.on
+    }
+
+    /// Get the `OFF` value including the `full OFF` flag in bit 12.
+    pub fn off_with_flag(self) -> u16 {
+        self.off
+    }
+
+    /// Whether the `full ON` flag is set.
+    ///
+    /// Note that the `full OFF` flag takes precedence.
+    pub fn full_on(self) -> bool {
+        self.on & 0x1000 != 0
+    }
+
+    /// Whether the `full OFF` flag is set.
+    pub fn full_off(self) -> bool {
+        self.off & 0x1000 != 0
+    }
+}
+
+impl From<[u8; 4]> for ChannelRegisters {
+    fn from(bytes: [u8; 4]) -> Self {
+        ChannelRegisters::from_bytes(bytes)
+    }
+}
+
+impl From<ChannelRegisters> for [u8; 4] {
+    fn from(registers: ChannelRegisters) -> Self {
+        registers.to_bytes()
+    }
+}
+
 /// Additional programmable address types (volatile programming)
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ProgrammableAddress {
@@ -176,6 +505,50 @@ pub enum ProgrammableAddress {
     AllCall,
 }
 
+/// Suspicious addressing configuration found by `check_addressing()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressingIssue {
+    /// The driver communicates through the enabled LED all call address,
+    /// so every device on the bus responding to it is affected
+    DriverUsesAllCall,
+    /// The programmable address is enabled but still has its power-on value
+    Unset(ProgrammableAddress),
+    /// The programmable address is enabled but has a reserved value
+    Invalid(ProgrammableAddress),
+    /// The programmable address is enabled and has the same value as the other one
+    Duplicate(ProgrammableAddress, ProgrammableAddress),
+}
+
+/// Override of the `full ON/OFF` flags of a channel in
+/// `Pca9685::apply_scene_with_flags()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlagState {
+    /// Use the flags contained in the scene values (default)
+    Keep,
+    /// Set the `full ON` flag and clear the `full OFF` flag
+    FullOn,
+    /// Set the `full OFF` flag
+    FullOff,
+}
+
+impl Default for FlagState {
+    fn default() -> Self {
+        FlagState::Keep
+    }
+}
+
+/// Pulse of a channel decoded from its `ON` and `OFF` register values
+/// by `pulse_from_raw()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PulseResult {
+    /// The `full ON` flag is set and the output is always on
+    Full,
+    /// The `full OFF` flag is set and the output is always off
+    Off,
+    /// The output is driven by the counters with a pulse of this many ticks
+    Length(u16),
+}
+
 /// I2C device address
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Address(pub(crate) u8);
@@ -213,6 +586,28 @@ impl From<(bool, bool, bool, bool, bool, bool)> for Address {
 mod tests {
     use super::*;
 
+    #[test]
+    fn can_decode_raw_counter() {
+        let counter = RawCounter::from_raw(0xF123);
+        assert_eq!(0x123, counter.count);
+        assert!(counter.full);
+        assert!(!RawCounter::from_raw(0x0FFF).full);
+    }
+
+    #[test]
+    fn default_instance_has_power_on_configuration() {
+        let pwm = Pca9685::<()>::default();
+        assert_eq!(DEVICE_BASE_ADDRESS, pwm.address);
+        assert_eq!(PRESCALE_DEFAULT, pwm.prescale);
+        assert_eq!(INTERNAL_OSCILLATOR_HZ, pwm.oscillator_hz);
+        assert_eq!(INTERNAL_OSCILLATOR_HZ, pwm.internal_oscillator_hz);
+    }
+
+    #[test]
+    fn can_wrap_i2c_error() {
+        assert_eq!(Error::I2C(5), Error::from_i2c(5));
+    }
+
     macro_rules! default_test {
         ($name:ident, $type:ident, $default:ident) => {
             #[test]
@@ -226,6 +621,26 @@ mod tests {
     default_test!(default_out_change, OutputStateChange, OnStop);
     default_test!(default_out_driver, OutputDriver, TotemPole);
     default_test!(default_disabled_out_value, DisabledOutputValue, Zero);
+    default_test!(default_flag_state, FlagState, Keep);
+
+    #[test]
+    fn channel_registers_from_bytes() {
+        let regs = ChannelRegisters::from_bytes([0x34, 0xF2, 0x78, 0x16]);
+        assert_eq!(0x234, regs.on());
+        assert_eq!(0x678, regs.off());
+        assert_eq!(0x1234, regs.on_with_flag());
+        assert_eq!(0x1678, regs.off_with_flag());
+        assert!(regs.full_on());
+        assert!(regs.full_off());
+    }
+
+    #[test]
+    fn channel_registers_to_bytes() {
+        let regs = ChannelRegisters::new(0x0123, 0xF456);
+        assert!(!regs.full_on());
+        assert!(regs.full_off());
+        assert_eq!([0x23, 0x01, 0x56, 0x14], <[u8; 4]>::from(regs));
+    }
 
     #[test]
     fn can_get_default_address() {
@@ -1,9 +1,13 @@
-use embedded_hal_mock::i2c::Transaction as I2cTrans;
-use pwm_pca9685::{Address, ProgrammableAddress as ProgAddr};
+use embedded_hal_mock::{
+    i2c::{Mock as I2cMock, Transaction as I2cTrans},
+    MockError,
+};
+use pwm_pca9685::{Address, AddressingIssue, Error, Pca9685, ProgrammableAddress as ProgAddr};
+use std::io::ErrorKind;
 
 mod common;
 use self::common::{
-    assert_invalid_input_data, destroy, new, BitFlags, Register, DEV_ADDR, MODE1_DEFAULT,
+    assert_invalid_input_data, destroy, new, BitFlags, Register, DEV_ADDR, MODE1_AI, MODE1_DEFAULT,
 };
 
 invalid_test!(cannot_set_address_0, set_address, 0);
@@ -33,11 +37,21 @@ macro_rules! prog_addr_test {
                 0
             );
 
+            #[test]
+            fn cannot_set_device_address() {
+                let mut pwm = new(&[]);
+                assert_error!(
+                    pwm.set_programmable_address(ProgAddr::$variant, DEV_ADDR),
+                    Error::AddressConflict
+                );
+                destroy(pwm);
+            }
+
             call_method_test!(
                 can_set,
                 set_programmable_address,
                 $reg,
-                0x71,
+                0xE2,
                 ProgAddr::$variant,
                 0x71
             );
@@ -109,3 +123,261 @@ fn can_generate_alternative_addresses() {
         Address::from((true, true, true, true, true, true))
     );
 }
+
+fn scan_transactions() -> Vec<I2cTrans> {
+    (0x40..=0x7F)
+        .filter(|a| *a != 0x70)
+        .map(|a| {
+            let t = I2cTrans::write_read(a, vec![Register::MODE1], vec![MODE1_DEFAULT]);
+            if a == 0x41 || a == 0x7F {
+                t
+            } else {
+                t.with_error(MockError::Io(ErrorKind::Other))
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn can_scan_into() {
+    let mut i2c = I2cMock::new(&scan_transactions());
+    let mut found = [0; 4];
+    let count = Pca9685::scan_into(&mut i2c, &mut found);
+    assert_eq!(2, count);
+    assert_eq!([0x41, 0x7F], found[..count]);
+    i2c.done();
+}
+
+#[test]
+fn scan_into_stops_when_full() {
+    let trans: Vec<_> = scan_transactions().into_iter().take(2).collect();
+    let mut i2c = I2cMock::new(&trans);
+    let mut found = [0; 1];
+    assert_eq!(1, Pca9685::scan_into(&mut i2c, &mut found));
+    assert_eq!([0x41], found);
+    i2c.done();
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn can_scan() {
+    let mut i2c = I2cMock::new(&scan_transactions());
+    let found = Pca9685::scan(&mut i2c);
+    assert_eq!(&[0x41, 0x7F], &found[..]);
+    i2c.done();
+}
+
+#[test]
+fn cannot_set_programmable_address_to_changed_address() {
+    let mut pwm = new(&[]);
+    pwm.set_address(0x71).unwrap();
+    assert_error!(
+        pwm.set_programmable_address(ProgAddr::Subaddress1, 0x71),
+        Error::AddressConflict
+    );
+    destroy(pwm);
+}
+
+fn addressing_transactions(mode1: u8, addresses: [u8; 4]) -> [I2cTrans; 2] {
+    let mut data = vec![mode1 | BitFlags::AUTO_INC, 0];
+    data.extend(addresses.iter().map(|a| a << 1));
+    [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], data),
+    ]
+}
+
+#[test]
+fn default_addressing_is_fine() {
+    let trans = addressing_transactions(MODE1_DEFAULT, [0x71, 0x72, 0x74, 0x70]);
+    let mut pwm = new(&trans);
+    pwm.check_addressing().unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn configured_subaddresses_are_fine() {
+    let mode1 = MODE1_DEFAULT | BitFlags::SUBADDR1 | BitFlags::SUBADDR3;
+    let trans = addressing_transactions(mode1, [0x50, 0x72, 0x51, 0x70]);
+    let mut pwm = new(&trans);
+    pwm.check_addressing().unwrap();
+    destroy(pwm);
+}
+
+macro_rules! addressing_issue_test {
+    ($name:ident, $mode1:expr, $addresses:expr, $issue:expr) => {
+        #[test]
+        fn $name() {
+            let trans = addressing_transactions($mode1, $addresses);
+            let mut pwm = new(&trans);
+            match pwm.check_addressing() {
+                Err(Error::Addressing(issue)) => assert_eq!($issue, issue),
+                _ => panic!("Error::Addressing not returned."),
+            }
+            destroy(pwm);
+        }
+    };
+}
+
+addressing_issue_test!(
+    detects_driver_using_all_call,
+    MODE1_DEFAULT,
+    [0x71, 0x72, 0x74, DEV_ADDR],
+    AddressingIssue::DriverUsesAllCall
+);
+addressing_issue_test!(
+    detects_unset_subaddress,
+    MODE1_DEFAULT | BitFlags::SUBADDR2,
+    [0x71, 0x72, 0x74, 0x70],
+    AddressingIssue::Unset(ProgAddr::Subaddress2)
+);
+addressing_issue_test!(
+    detects_invalid_subaddress,
+    MODE1_DEFAULT | BitFlags::SUBADDR3,
+    [0x71, 0x72, 0x03, 0x70],
+    AddressingIssue::Invalid(ProgAddr::Subaddress3)
+);
+addressing_issue_test!(
+    detects_duplicate_addresses,
+    MODE1_DEFAULT | BitFlags::SUBADDR1,
+    [0x70, 0x72, 0x74, 0x70],
+    AddressingIssue::Duplicate(ProgAddr::Subaddress1, ProgAddr::AllCall)
+);
+
+#[test]
+fn can_configure_subaddresses() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::SUBADDR1, 0x50 << 1]),
+        I2cTrans::write(DEV_ADDR, vec![Register::SUBADDR3, 0x51 << 1]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Register::MODE1,
+                MODE1_DEFAULT | BitFlags::SUBADDR1 | BitFlags::SUBADDR3,
+            ],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::SUBADDR2, 0x52 << 1]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT | BitFlags::SUBADDR2],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.configure_subaddresses([Some(0x50), None, Some(0x51)])
+        .unwrap();
+    pwm.configure_subaddresses([None, Some(0x52), None])
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_configure_invalid_subaddress() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.configure_subaddresses([Some(0x50), Some(0x03), None]));
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_configure_subaddress_as_device_address() {
+    let mut pwm = new(&[]);
+    assert_error!(
+        pwm.configure_subaddresses([Some(0x50), None, Some(DEV_ADDR)]),
+        Error::AddressConflict
+    );
+    destroy(pwm);
+}
+
+#[test]
+fn can_check_all_call_enabled() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![MODE1_DEFAULT]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::MODE1],
+            vec![MODE1_DEFAULT & !BitFlags::ALL_CALL_ADDR],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    assert!(pwm.is_all_call_enabled().unwrap());
+    assert!(!pwm.is_all_call_enabled().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_reprogram_and_retarget() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::SUBADDR2, 0x71 << 1]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT | BitFlags::SUBADDR2],
+        ),
+        I2cTrans::write(0x71, vec![Register::PRE_SCALE, 3]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.reprogram_and_retarget(ProgAddr::Subaddress2, 0x71)
+        .unwrap();
+    pwm.set_prescale(3).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn failed_reprogramming_keeps_address() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::SUBADDR1, 0x71 << 1]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT | BitFlags::SUBADDR1],
+        )
+        .with_error(MockError::Io(ErrorKind::Other)),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 3]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.reprogram_and_retarget(ProgAddr::Subaddress1, 0x71)
+        .unwrap_err();
+    pwm.set_prescale(3).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_reprogram_and_retarget_to_invalid_address() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.reprogram_and_retarget(ProgAddr::Subaddress1, 0x70));
+    destroy(pwm);
+}
+
+#[test]
+fn can_get_programmable_address() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::SUBADDR2], vec![0x51 << 1]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::ALL_CALL_ADDR], vec![0x70 << 1]),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(
+        0x51,
+        pwm.get_programmable_address(ProgAddr::Subaddress2).unwrap()
+    );
+    assert_eq!(
+        0x70,
+        pwm.get_programmable_address(ProgAddr::AllCall).unwrap()
+    );
+    destroy(pwm);
+}
+
+#[test]
+fn can_get_subaddress_config() {
+    let mode1 = MODE1_DEFAULT | BitFlags::SUBADDR1 | BitFlags::SUBADDR3;
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::MODE1],
+            vec![mode1, 0x04, 0x50 << 1, 0x72 << 1, 0x51 << 1],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(
+        [(0x50, true), (0x72, false), (0x51, true)],
+        pwm.subaddress_config().unwrap()
+    );
+    destroy(pwm);
+}
@@ -0,0 +1,162 @@
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use pwm_pca9685::{build_gamma_lut, Channel};
+
+mod common;
+use crate::common::{assert_invalid_input_data, destroy, new, Register, DEV_ADDR, MODE1_AI};
+
+#[test]
+fn can_set_all_channels_brightness_lut() {
+    let mut data = vec![Register::C0_ON_L];
+    // channel 0 off, channel 1 on, the rest at level 128
+    data.extend_from_slice(&[0, 0, 0, 0x10, 0, 0x10, 0, 0]);
+    for _ in 2..16 {
+        data.extend_from_slice(&[0, 0, 0x83, 0x03]);
+    }
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, data),
+    ];
+    let mut pwm = new(&trans);
+    let mut levels = [128; 16];
+    levels[0] = 0;
+    levels[1] = 255;
+    pwm.set_all_channels_brightness_lut(&levels, &build_gamma_lut(2.2))
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn brightness_lut_applies_on_offsets() {
+    let mut data = vec![Register::C0_ON_L];
+    data.extend_from_slice(&[0, 0x01, 0x10, 0x01]);
+    for _ in 1..16 {
+        data.extend_from_slice(&[0, 0, 0x10, 0]);
+    }
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, data),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_offset(Channel::C0, 0x100).unwrap();
+    pwm.set_all_channels_brightness_lut(&[1; 16], &build_gamma_lut(1.0))
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_use_invalid_lut() {
+    let mut pwm = new(&[]);
+    let mut lut = build_gamma_lut(2.2);
+    lut[3] = 4097;
+    assert_invalid_input_data(pwm.set_all_channels_brightness_lut(&[3; 16], &lut));
+    destroy(pwm);
+}
+
+#[test]
+fn can_apply_curve() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_OFF_L, 0xE8, 0x03]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.apply_curve(Channel::C0, &[0, 1000, 4095], 1).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn apply_curve_wraps_index() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_OFF_L, 0xE8, 0x03]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.apply_curve(Channel::C0, &[0, 1000, 4095], 4).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_apply_empty_curve() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.apply_curve(Channel::C0, &[], 0));
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_apply_curve_with_invalid_sample() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.apply_curve(Channel::C0, &[0, 4096], 1));
+    destroy(pwm);
+}
+
+fn budgeted_trans(off: [u16; 16]) -> [I2cTrans; 2] {
+    let mut data = vec![Register::C0_ON_L];
+    for off in off.iter() {
+        match off {
+            0 => data.extend_from_slice(&[0, 0, 0, 0x10]),
+            4096 => data.extend_from_slice(&[0, 0x10, 0, 0]),
+            off => data.extend_from_slice(&[0, 0, *off as u8, (*off >> 8) as u8]),
+        }
+    }
+    [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, data),
+    ]
+}
+
+#[test]
+fn budgeted_channels_within_budget_are_kept() {
+    let mut desired = [1000; 16];
+    desired[0] = 4096;
+    desired[1] = 0;
+    let mut pwm = new(&budgeted_trans(desired));
+    pwm.set_channels_budgeted(&desired, 19096).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn budgeted_channels_are_scaled_down() {
+    let mut desired = [1000; 16];
+    desired[0] = 4096;
+    desired[1] = 0;
+    // sum = 18096, scaled by 9048 / 18096 = 0.5
+    let mut expected = [500; 16];
+    expected[0] = 2048;
+    expected[1] = 0;
+    let mut pwm = new(&budgeted_trans(expected));
+    pwm.set_channels_budgeted(&desired, 9048).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn budgeted_channels_round_down() {
+    let desired = [3; 16];
+    // 3 * 47 / 48 = 2.94
+    let mut pwm = new(&budgeted_trans([2; 16]));
+    pwm.set_channels_budgeted(&desired, 47).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn budgeted_channels_snap_down_to_min_on() {
+    let mut desired = [150; 16];
+    desired[0] = 400;
+    desired[1] = 90;
+    // sum = 2590, scaled by 1295 / 2590 = 0.5 -> 200, 45 and 75
+    let mut expected = [0; 16];
+    expected[0] = 200;
+    let mut pwm = new(&budgeted_trans(expected));
+    pwm.set_channel_min_on(Channel::All, 100).unwrap();
+    pwm.set_channels_budgeted(&desired, 1295).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_budgeted_channels_invalid_value() {
+    let mut pwm = new(&[]);
+    let mut desired = [0; 16];
+    desired[4] = 4097;
+    assert_invalid_input_data(pwm.set_channels_budgeted(&desired, 100_000));
+    destroy(pwm);
+}
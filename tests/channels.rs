@@ -1,9 +1,17 @@
-use embedded_hal_mock::i2c::Transaction as I2cTrans;
-use pwm_pca9685::Channel;
-use std::convert::TryFrom;
+use core::cell::RefCell;
+use embedded_hal::PwmPin;
+use embedded_hal_mock::{i2c::Transaction as I2cTrans, MockError};
+use pwm_pca9685::{
+    pulse_from_raw, Channel, ChannelRegisters, Error, FlagState, OutputLogicState,
+    ParseChannelError, Pca9685, PulseResult, RawCounter, MAX_DUTY, RESOLUTION_BITS,
+};
+use std::{convert::TryFrom, io::ErrorKind};
 
 mod common;
-use self::common::{assert_invalid_input_data, destroy, new, Register, DEV_ADDR, MODE1_AI};
+use self::common::{
+    assert_invalid_input_data, destroy, new, BitFlags, Register, DEV_ADDR, MODE1_AI, MODE1_DEFAULT,
+    MODE2_DEFAULT,
+};
 
 macro_rules! can_convert_channel {
     ($t:ty, $($value:expr, $channel:ident),*) => {
@@ -13,6 +21,15 @@ macro_rules! can_convert_channel {
     };
 }
 
+#[test]
+fn resolution_is_12_bits() {
+    let pwm = new(&[]);
+    assert_eq!(12, RESOLUTION_BITS);
+    assert_eq!(4095, MAX_DUTY);
+    assert_eq!(4096, pwm.duty_levels());
+    destroy(pwm);
+}
+
 #[test]
 fn can_convert_channel_u8() {
     can_convert_channel!(
@@ -44,6 +61,26 @@ fn convert_channel_out_of_bounds() {
     assert_eq!(Err(()), Channel::try_from(16_usize));
 }
 
+#[test]
+fn can_parse_and_display_channel() {
+    for index in 0..16_u8 {
+        let channel = Channel::try_from(index).unwrap();
+        let name = format!("{}", channel);
+        assert_eq!(format!("C{}", index), name);
+        assert_eq!(Ok(channel), name.parse::<Channel>());
+    }
+    assert_eq!("All", format!("{}", Channel::All));
+    assert_eq!(Ok(Channel::All), "All".parse::<Channel>());
+    assert_eq!(Ok(Channel::C5), Channel::try_from("C5"));
+}
+
+#[test]
+fn cannot_parse_invalid_channel() {
+    for name in &["", "C", "C16", "C05", "C+5", "c5", "5", "all", "C1x"] {
+        assert_eq!(Err(ParseChannelError), name.parse::<Channel>());
+    }
+}
+
 invalid_test!(
     cannot_set_channel_on_invalid_value,
     set_channel_on,
@@ -51,13 +88,6 @@ invalid_test!(
     4096
 );
 
-invalid_test!(
-    cannot_set_channel_full_on_invalid_value,
-    set_channel_full_on,
-    Channel::C0,
-    4096
-);
-
 invalid_test!(
     cannot_set_channel_off_invalid_value,
     set_channel_off,
@@ -95,14 +125,65 @@ invalid_test!(
     &[4096; 16]
 );
 
+#[test]
+fn can_get_channel_registers() {
+    assert_eq!(Register::C0_ON_L, Channel::C0.on_register());
+    assert_eq!(Register::C0_OFF_L, Channel::C0.off_register());
+    assert_eq!(Register::C9_ON_L, Channel::C9.on_register());
+    assert_eq!(Register::C15_OFF_L, Channel::C15.off_register());
+    assert_eq!(Register::ALL_C_ON_L, Channel::All.on_register());
+    assert_eq!(Register::ALL_C_OFF_L, Channel::All.off_register());
+}
+
+#[test]
+fn set_channel_on_off_writes_equal_values() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 2, 1, 2, 1]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_off(Channel::C1, 0x102, 0x102).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_channel_on_off_strict() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 2, 1, 4, 3]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_off_strict(Channel::C1, 0x102, 0x304)
+        .unwrap();
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_channel_on_off_strict_equal_values,
+    set_channel_on_off_strict,
+    Channel::C1,
+    0x102,
+    0x102
+);
+
+invalid_test!(
+    cannot_set_channel_on_off_strict_invalid_value,
+    set_channel_on_off_strict,
+    Channel::C1,
+    0,
+    4096
+);
+
 #[test]
 fn sets_autoincrement_just_once() {
     let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::ALL_C_ON_L + 1], vec![0]),
         I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
         I2cTrans::write(
             DEV_ADDR,
             vec![Register::ALL_C_ON_L, 0b1111_1111, 0b0000_1111],
         ),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::ALL_C_ON_L + 1], vec![0]),
         I2cTrans::write(
             DEV_ADDR,
             vec![Register::ALL_C_ON_L, 0b1111_1111, 0b0000_1111],
@@ -124,6 +205,7 @@ macro_rules! channels_test {
 
                 fn can_set_channel_on_min() {
                     let trans = [
+                        I2cTrans::write_read(DEV_ADDR, vec![Register::$reg_on + 1], vec![0]),
                         I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
                         I2cTrans::write(DEV_ADDR, vec![Register::$reg_on, 0, 0])
                     ];
@@ -136,6 +218,7 @@ macro_rules! channels_test {
 
                 fn can_set_channel_on_max() {
                     let trans = [
+                        I2cTrans::write_read(DEV_ADDR, vec![Register::$reg_on + 1], vec![0]),
                         I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
                         I2cTrans::write(DEV_ADDR, vec![Register::$reg_on, 0b1111_1111, 0b0000_1111])
                     ];
@@ -147,6 +230,7 @@ macro_rules! channels_test {
                 #[test]
                 fn can_set_channel_off_min() {
                     let trans = [
+                        I2cTrans::write_read(DEV_ADDR, vec![Register::$reg_off + 1], vec![0]),
                         I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
                         I2cTrans::write(DEV_ADDR, vec![Register::$reg_off, 0, 0])
                     ];
@@ -158,6 +242,7 @@ macro_rules! channels_test {
                 #[test]
                 fn can_set_channel_off_max() {
                     let trans = [
+                        I2cTrans::write_read(DEV_ADDR, vec![Register::$reg_off + 1], vec![0]),
                         I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
                         I2cTrans::write(DEV_ADDR, vec![Register::$reg_off, 0b1111_1111, 0b0000_1111])
                     ];
@@ -168,37 +253,45 @@ macro_rules! channels_test {
 
                 #[test]
 
-                fn can_set_channel_full_on_min() {
+                fn can_set_channel_full_on() {
                     let trans = [
-                        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
-                        I2cTrans::write(DEV_ADDR, vec![Register::$reg_on, 0, 0b0001_0000])
+                        I2cTrans::write_read(DEV_ADDR, vec![Register::$reg_on + 1], vec![0b0000_0101]),
+                        I2cTrans::write(DEV_ADDR, vec![Register::$reg_on + 1, 0b0001_0101])
                     ];
                     let mut pwm = new(&trans);
-                    pwm.set_channel_full_on(Channel::$channel, 0).unwrap();
+                    pwm.set_channel_full_on(Channel::$channel, true).unwrap();
                     destroy(pwm);
                 }
 
                 #[test]
-
-                fn can_set_channel_full_on_max() {
+                fn can_clear_channel_full_on() {
                     let trans = [
-                        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
-                        I2cTrans::write(DEV_ADDR, vec![Register::$reg_on, 0b1111_1111, 0b0001_1111])
+                        I2cTrans::write_read(DEV_ADDR, vec![Register::$reg_on + 1], vec![0b0001_0101]),
+                        I2cTrans::write(DEV_ADDR, vec![Register::$reg_on + 1, 0b0000_0101])
                     ];
                     let mut pwm = new(&trans);
-                    pwm.set_channel_full_on(Channel::$channel, 4095).unwrap();
+                    pwm.set_channel_full_on(Channel::$channel, false).unwrap();
                     destroy(pwm);
                 }
 
                 #[test]
-
                 fn can_set_channel_full_off() {
                     let trans = [
-                        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
-                        I2cTrans::write(DEV_ADDR, vec![Register::$reg_off, 0, 0b0001_0000])
+                        I2cTrans::write_read(DEV_ADDR, vec![Register::$reg_off + 1], vec![0b0000_0101]),
+                        I2cTrans::write(DEV_ADDR, vec![Register::$reg_off + 1, 0b0001_0101])
+                    ];
+                    let mut pwm = new(&trans);
+                    pwm.set_channel_full_off(Channel::$channel, true).unwrap();
+                    destroy(pwm);
+                }
+
+                #[test]
+                fn full_off_is_not_written_if_already_set() {
+                    let trans = [
+                        I2cTrans::write_read(DEV_ADDR, vec![Register::$reg_off + 1], vec![0b0001_0000]),
                     ];
                     let mut pwm = new(&trans);
-                    pwm.set_channel_full_off(Channel::$channel).unwrap();
+                    pwm.set_channel_full_off(Channel::$channel, true).unwrap();
                     destroy(pwm);
                 }
 
@@ -360,3 +453,1625 @@ fn can_set_all_on_off() {
     pwm.set_all_on_off(&on, &off).unwrap();
     destroy(pwm);
 }
+
+#[test]
+fn can_write_channels_from() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C14_ON_L, 0x02, 0x01, 0x04, 0x03, 0, 0, 0xFF, 0x0F],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.write_channels_from(Channel::C14, &[(0x102, 0x304), (0, 4095)])
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn write_channels_from_empty_slice_does_nothing() {
+    let mut pwm = new(&[]);
+    pwm.write_channels_from(Channel::C3, &[]).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn channel_mapping_is_used_for_writing_channels_from() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C15_ON_L, 1, 0, 2, 0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C14_ON_L, 3, 0, 4, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_mapping(REVERSED).unwrap();
+    pwm.write_channels_from(Channel::C0, &[(1, 2), (3, 4)])
+        .unwrap();
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_write_channels_from_past_last_channel,
+    write_channels_from,
+    Channel::C15,
+    &[(0, 0), (0, 0)]
+);
+invalid_test!(
+    cannot_write_channels_from_all,
+    write_channels_from,
+    Channel::All,
+    &[(0, 0)]
+);
+invalid_test!(
+    cannot_write_channels_from_invalid_value,
+    write_channels_from,
+    Channel::C0,
+    &[(0, 0x2000)]
+);
+
+#[test]
+fn can_set_all_channels_on_off_with_flags() {
+    let mut data = vec![Register::C0_ON_L, 0x02, 0x01, 0, 0x10];
+    data.extend_from_slice(&[0; 56]);
+    data.extend_from_slice(&[0, 0x10, 0xFF, 0x0F]);
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, data),
+    ];
+    let mut pwm = new(&trans);
+    let mut values = [0; 32];
+    values[0] = 0x102;
+    values[1] = 0x1000;
+    values[30] = 0x1000;
+    values[31] = 0xFFF;
+    pwm.set_all_channels_on_off_with_flags(&values).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_apply_scene() {
+    let mut data = vec![Register::C0_ON_L];
+    for _ in 0..16 {
+        data.extend_from_slice(&[0x00, 0x01, 0x00, 0x04]);
+    }
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, data),
+    ];
+    let mut pwm = new(&trans);
+    let mut scene = [0x100; 32];
+    for off in scene.iter_mut().skip(1).step_by(2) {
+        *off = 0x400;
+    }
+    pwm.apply_scene(&scene).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_apply_scene_with_flags() {
+    let mut data = vec![Register::C0_ON_L];
+    data.extend_from_slice(&[0x00, 0x11, 0x00, 0x04]);
+    data.extend_from_slice(&[0x00, 0x11, 0x00, 0x04]);
+    data.extend_from_slice(&[0x00, 0x01, 0x00, 0x14]);
+    for _ in 3..16 {
+        data.extend_from_slice(&[0x00, 0x01, 0x00, 0x04]);
+    }
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, data),
+    ];
+    let mut pwm = new(&trans);
+    let mut scene = [0x100; 32];
+    for off in scene.iter_mut().skip(1).step_by(2) {
+        *off = 0x400;
+    }
+    // The full OFF flag of channel 1 is cleared by the override.
+    scene[3] = 0x1400;
+    let mut flags = [FlagState::Keep; 16];
+    flags[0] = FlagState::FullOn;
+    flags[1] = FlagState::FullOn;
+    flags[2] = FlagState::FullOff;
+    pwm.apply_scene_with_flags(&scene, &flags).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_apply_invalid_scene_with_flags() {
+    let mut pwm = new(&[]);
+    let mut scene = [0; 32];
+    scene[0] = 0x2000;
+    let flags = [FlagState::FullOff; 16];
+    assert_invalid_input_data(pwm.apply_scene_with_flags(&scene, &flags));
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_all_channels_on_off_with_invalid_flags() {
+    let mut pwm = new(&[]);
+    let mut values = [0; 32];
+    values[7] = 0x2000;
+    assert_invalid_input_data(pwm.set_all_channels_on_off_with_flags(&values));
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_channel_off_range() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Register::C2_ON_L,
+                0,
+                0,
+                0x34,
+                0x02,
+                0,
+                0,
+                0x34,
+                0x02,
+                0,
+                0,
+                0x34,
+                0x02,
+            ],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_off_range(Channel::C2, Channel::C4, 0x234)
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_channel_off_range_of_one_channel() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C15_ON_L, 0, 0, 0xFF, 0x0F]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_off_range(Channel::C15, Channel::C15, 4095)
+        .unwrap();
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_channel_off_range_reversed,
+    set_channel_off_range,
+    Channel::C4,
+    Channel::C2,
+    0
+);
+invalid_test!(
+    cannot_set_channel_off_range_from_all,
+    set_channel_off_range,
+    Channel::All,
+    Channel::C2,
+    0
+);
+invalid_test!(
+    cannot_set_channel_off_range_to_all,
+    set_channel_off_range,
+    Channel::C0,
+    Channel::All,
+    0
+);
+invalid_test!(
+    cannot_set_channel_off_range_invalid_value,
+    set_channel_off_range,
+    Channel::C0,
+    Channel::C1,
+    4096
+);
+
+#[test]
+fn can_write_channels_from_with_flags() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C0_ON_L, 0, 0x10, 0, 0, 0, 0, 0, 0x10],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.write_channels_from(Channel::C0, &[(0x1000, 0), (0, 0x1000)])
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_zero_all_on() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::ALL_C_ON_L, 0, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.zero_all_on().unwrap();
+    destroy(pwm);
+}
+
+macro_rules! all_channels_flags_test {
+    ($name:ident, $full_on:expr, $full_off:expr, $on_h:expr, $off_h:expr) => {
+        #[test]
+        fn $name() {
+            let trans = [
+                I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+                I2cTrans::write(DEV_ADDR, vec![Register::ALL_C_ON_L, 0, $on_h, 0, $off_h]),
+            ];
+            let mut pwm = new(&trans);
+            pwm.set_all_channels_flags($full_on, $full_off).unwrap();
+            destroy(pwm);
+        }
+    };
+}
+
+all_channels_flags_test!(can_set_all_channels_full_on, true, false, 0x10, 0);
+all_channels_flags_test!(can_set_all_channels_full_off, false, true, 0, 0x10);
+all_channels_flags_test!(can_clear_all_channels_flags, false, false, 0, 0);
+
+#[test]
+fn cannot_set_all_channels_full_on_and_off() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_all_channels_flags(true, true));
+    destroy(pwm);
+}
+
+#[test]
+fn can_get_all_channels_on_off_with_flags() {
+    let mut data = vec![0; 64];
+    data[0] = 0x02;
+    data[1] = 0x01;
+    data[3] = 0x10;
+    data[62] = 0xFF;
+    data[63] = 0x1F;
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data),
+    ];
+    let mut pwm = new(&trans);
+    let values = pwm.get_all_channels_on_off_with_flags().unwrap();
+    let mut expected = [0; 32];
+    expected[0] = 0x102;
+    expected[1] = 0x1000;
+    expected[31] = 0x1FFF;
+    assert_eq!(expected, values);
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_global_phase() {
+    let mut current = vec![0; 64];
+    // channel 0: 1024 ticks, channel 1: full off
+    current[2..4].copy_from_slice(&[0x00, 0x04]);
+    current[7] = 0x10;
+    // channel 2: full on, channel 3: 2048 ticks wrapping around
+    current[9] = 0x10;
+    current[12..16].copy_from_slice(&[0x00, 0x0C, 0x00, 0x04]);
+    let mut data = vec![Register::C0_ON_L];
+    data.extend_from_slice(&[0x00, 0x08, 0x00, 0x0C]);
+    data.extend_from_slice(&[0, 0, 0, 0x10]);
+    data.extend_from_slice(&[0, 0x10, 0, 0]);
+    data.extend_from_slice(&[0x00, 0x08, 0, 0]);
+    for _ in 4..16 {
+        data.extend_from_slice(&[0, 0, 0, 0x10]);
+    }
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], current),
+        I2cTrans::write(DEV_ADDR, data),
+        I2cTrans::write(DEV_ADDR, vec![Register::C5_ON_L, 0x00, 0x08, 0x00, 0x0C]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_global_phase(0.5).unwrap();
+    // The phase is kept by later updates.
+    pwm.set_channel_simple(Channel::C5, 1024).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_global_phase_out_of_range() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_global_phase(1.0));
+    assert_invalid_input_data(pwm.set_global_phase(-0.1));
+    assert_invalid_input_data(pwm.set_global_phase(f32::NAN));
+    destroy(pwm);
+}
+
+fn all_channels_trans() -> [I2cTrans; 2] {
+    let mut data = vec![0; 64];
+    data[0..4].copy_from_slice(&[0x02, 0x11, 0x04, 0x03]);
+    data[60..64].copy_from_slice(&[0xFF, 0x0F, 0xFF, 0x1F]);
+    [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data),
+    ]
+}
+
+#[test]
+fn can_get_all_channels_on() {
+    let mut pwm = new(&all_channels_trans());
+    let mut expected = [0; 16];
+    expected[0] = 0x102;
+    expected[15] = 0xFFF;
+    assert_eq!(expected, pwm.get_all_channels_on().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_get_all_channels_off() {
+    let mut pwm = new(&all_channels_trans());
+    let mut expected = [0; 16];
+    expected[0] = 0x304;
+    expected[15] = 0xFFF;
+    assert_eq!(expected, pwm.get_all_channels_off().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_get_all_duty_cycles() {
+    let mut data = vec![0; 64];
+    data[..4].copy_from_slice(&[0, 0x01, 0, 0x09]);
+    data[4..8].copy_from_slice(&[0, 0x10, 0, 0]);
+    data[8..12].copy_from_slice(&[0, 0x10, 0, 0x10]);
+    data[60..].copy_from_slice(&[0, 0x0C, 0, 0x04]);
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data),
+    ];
+    let mut pwm = new(&trans);
+    let mut expected = [0.0; 16];
+    expected[0] = 0.5;
+    expected[1] = 1.0;
+    // Wraps around at the end of the period.
+    expected[15] = 0.5;
+    assert_eq!(expected, pwm.get_all_duty_cycles().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_count_simultaneous_edges() {
+    let mut data = vec![];
+    // Three pulses starting at 0x100, two at 0.
+    for _ in 0..3 {
+        data.extend_from_slice(&[0, 0x01, 0, 0x08]);
+    }
+    for _ in 0..2 {
+        data.extend_from_slice(&[0, 0, 0, 0x04]);
+    }
+    // Always on, always off and equal counters do not switch.
+    for _ in 0..4 {
+        data.extend_from_slice(&[0, 0x11, 0, 0]);
+        data.extend_from_slice(&[0, 0x01, 0, 0x10]);
+    }
+    for _ in 13..16 {
+        data.extend_from_slice(&[0, 0x01, 0, 0x01]);
+    }
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(3, pwm.count_simultaneous_edges().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_dump_channel_registers() {
+    let mut data = [0; 64];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data.to_vec()),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(data, pwm.dump_channel_registers().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn channel_mapping_is_not_used_for_dump() {
+    let mut data = [0; 64];
+    data[0] = 0xAB;
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data.to_vec()),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_mapping(REVERSED).unwrap();
+    assert_eq!(data, pwm.dump_channel_registers().unwrap());
+    destroy(pwm);
+}
+
+macro_rules! full_flag_query_test {
+    ($name:ident, $method:ident, $on_h:expr, $off_h:expr, $expected:expr) => {
+        #[test]
+        fn $name() {
+            let trans = [
+                I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+                I2cTrans::write_read(DEV_ADDR, vec![Register::C2_ON_L], vec![0, $on_h, 0, $off_h]),
+            ];
+            let mut pwm = new(&trans);
+            assert_eq!($expected, pwm.$method(Channel::C2).unwrap());
+            destroy(pwm);
+        }
+    };
+}
+
+full_flag_query_test!(fully_on, is_channel_fully_on, 0x10, 0, true);
+full_flag_query_test!(not_fully_on, is_channel_fully_on, 0x0F, 0, false);
+full_flag_query_test!(
+    fully_off_overrides_on,
+    is_channel_fully_on,
+    0x10,
+    0x10,
+    false
+);
+full_flag_query_test!(fully_off, is_channel_fully_off, 0x10, 0x10, true);
+full_flag_query_test!(not_fully_off, is_channel_fully_off, 0, 0x0F, false);
+
+invalid_test!(cannot_query_all_fully_on, is_channel_fully_on, Channel::All);
+invalid_test!(
+    cannot_query_all_fully_off,
+    is_channel_fully_off,
+    Channel::All
+);
+
+macro_rules! pwm8_test {
+    ($name:ident, $value:expr, $registers:expr) => {
+        #[test]
+        fn $name() {
+            let mut data = vec![Register::C3_ON_L];
+            data.extend_from_slice(&$registers);
+            let trans = [
+                I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+                I2cTrans::write(DEV_ADDR, data),
+            ];
+            let mut pwm = new(&trans);
+            pwm.set_channel_pwm8(Channel::C3, $value).unwrap();
+            destroy(pwm);
+        }
+    };
+}
+
+pwm8_test!(pwm8_zero_is_full_off, 0, [0, 0, 0, 0x10]);
+pwm8_test!(pwm8_one, 1, [0, 0, 16, 0]);
+pwm8_test!(pwm8_middle, 128, [0, 0, 0x07, 0x08]);
+pwm8_test!(pwm8_254, 254, [0, 0, 0xEE, 0x0F]);
+pwm8_test!(pwm8_max_is_full_on, 255, [0, 0x10, 0, 0]);
+
+#[test]
+fn set_channels_duty_sorts_channels() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 0, 0, 0, 4, 0, 0, 0, 8]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channels_duty(vec![(Channel::C2, 0.5), (Channel::C1, 0.25)])
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_channels_duty_uses_last_duplicate() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C5_ON_L, 0, 0, 0, 0x10]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channels_duty(vec![(Channel::C5, 1.0), (Channel::C5, 0.0)])
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_channels_duty_writes_each_run() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 0, 8]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C15_ON_L, 0, 0x10, 0, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channels_duty(vec![(Channel::C15, 1.0), (Channel::C0, 0.5)])
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_channels_duty_writes_nothing_if_invalid() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_channels_duty(vec![(Channel::C0, 0.5), (Channel::All, 0.5)]));
+    assert_invalid_input_data(pwm.set_channels_duty(vec![(Channel::C0, 0.5), (Channel::C1, 1.5)]));
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_channels_differential() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0, 0, 0x04]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C5_ON_L, 0, 0, 0, 0x0C]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C6_ON_L, 0, 0, 0, 0x10, 0, 0x10, 0, 0],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channels_differential(Channel::C5, Channel::C2, 0.75)
+        .unwrap();
+    pwm.set_channels_differential(Channel::C7, Channel::C6, 1.0)
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_channels_differential_writes_nothing_if_invalid() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_channels_differential(Channel::C0, Channel::C0, 0.5));
+    assert_invalid_input_data(pwm.set_channels_differential(Channel::C0, Channel::All, 0.5));
+    assert_invalid_input_data(pwm.set_channels_differential(Channel::C0, Channel::C1, 1.5));
+    assert_invalid_input_data(pwm.set_channels_differential(Channel::C0, Channel::C1, f32::NAN));
+    destroy(pwm);
+}
+
+#[test]
+fn set_channel_value_clears_stale_flags() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L + 1, 0x10]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_ON_L, 0, 0, 0x34, 0x02]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_full_off(Channel::C3, true).unwrap();
+    pwm.set_channel_value(Channel::C3, 0x234).unwrap();
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_channel_value_too_big,
+    set_channel_value,
+    Channel::C0,
+    4096
+);
+
+#[test]
+fn can_set_channel_max_on_with_full_flag() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 0, 0x10, 0, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_max_on(Channel::C1, true).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_channel_max_on_without_full_flag() {
+    // The output is low for the last tick of the period.
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 0, 0, 0xFF, 0x0F]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_max_on(Channel::C1, false).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn min_on_snaps_short_pulses() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 0, 0, 100, 0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 0, 0, 0, 0x10]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 0, 0, 200, 0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 40, 0, 0, 0, 100, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_min_on(Channel::C1, 100).unwrap();
+    pwm.set_channel_simple(Channel::C1, 50).unwrap();
+    pwm.set_channel_simple(Channel::C1, 49).unwrap();
+    pwm.set_channel_simple(Channel::C1, 200).unwrap();
+    // 0.015 * 4096 = 61.4
+    pwm.set_channels_duty(vec![(Channel::C0, 40.0 / 4096.0), (Channel::C1, 0.015)])
+        .unwrap();
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_channel_min_on_too_big,
+    set_channel_min_on,
+    Channel::C0,
+    4096
+);
+
+#[test]
+fn can_set_channel_on_and_width() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0x01, 0, 0x05]),
+        // 0xF00 + 0x200 wraps around to 0x100.
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0x0F, 0, 0x01]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_and_width(Channel::C2, 0x100, 0x400)
+        .unwrap();
+    pwm.set_channel_on_and_width(Channel::C2, 0xF00, 0x200)
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn channel_on_and_width_round_trip_through_effective_pulse() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        // 3000 + 2000 wraps around to 904.
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0xB8, 0x0B, 0x88, 0x03]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::C2_ON_L],
+            vec![0xB8, 0x0B, 0x88, 0x03],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0x01, 0, 0x05]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C2_ON_L], vec![0, 0x01, 0, 0x05]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_and_width(Channel::C2, 3000, 2000)
+        .unwrap();
+    assert_eq!(2000, pwm.get_effective_pulse(Channel::C2).unwrap());
+    pwm.set_channel_on_and_width(Channel::C2, 0x100, 0x400)
+        .unwrap();
+    assert_eq!(0x400, pwm.get_effective_pulse(Channel::C2).unwrap());
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_channel_on_and_width_invalid_on,
+    set_channel_on_and_width,
+    Channel::C0,
+    4096,
+    1
+);
+
+invalid_test!(
+    cannot_set_channel_on_and_width_invalid_width,
+    set_channel_on_and_width,
+    Channel::C0,
+    0,
+    4096
+);
+
+#[test]
+fn can_set_channel_simple() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, 0, 0, 0, 0x08]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_simple(Channel::C1, 2048).unwrap();
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_channel_simple_too_long,
+    set_channel_simple,
+    Channel::C1,
+    4097
+);
+
+#[test]
+fn can_set_channel_duty_q12() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C4_ON_L, 0, 0, 0xFF, 0x0F]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_duty_q12(Channel::C4, 4095).unwrap();
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_channel_duty_q12_too_big,
+    set_channel_duty_q12,
+    Channel::C4,
+    4096
+);
+
+#[test]
+fn can_get_channel_duty_q12() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::C4_ON_L],
+            vec![0x00, 0x01, 0x00, 0x09],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(0x800, pwm.get_channel_duty_q12(Channel::C4).unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_get_channel_permille() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C4_ON_L], vec![0, 0, 0, 0x04]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C4_ON_L], vec![0, 0x10, 0, 0]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C4_ON_L], vec![0, 0, 0, 0x10]),
+    ];
+    let mut pwm = new(&trans);
+    // 1024 / 4096
+    assert_eq!(250, pwm.get_channel_permille(Channel::C4).unwrap());
+    assert_eq!(1000, pwm.get_channel_permille(Channel::C4).unwrap());
+    assert_eq!(0, pwm.get_channel_permille(Channel::C4).unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_get_channel_duty_cycle() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C4_ON_L], vec![0, 0, 0, 0x04]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C4_ON_L], vec![0, 0x10, 0, 0]),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(0.25, pwm.get_channel_duty_cycle(Channel::C4).unwrap());
+    assert_eq!(1.0, pwm.get_channel_duty_cycle(Channel::C4).unwrap());
+    assert_invalid_input_data(pwm.get_channel_duty_cycle(Channel::All));
+    destroy(pwm);
+}
+
+#[test]
+fn logical_duty_cycle_accounts_for_inverted_outputs() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C4_ON_L], vec![0, 0, 0, 0x04]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE2, MODE2_DEFAULT | BitFlags::INVRT],
+        ),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C4_ON_L], vec![0, 0, 0, 0x04]),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(
+        0.25,
+        pwm.get_channel_duty_cycle_logical(Channel::C4).unwrap()
+    );
+    pwm.set_output_logic_state(OutputLogicState::Inverted)
+        .unwrap();
+    assert_eq!(
+        0.75,
+        pwm.get_channel_duty_cycle_logical(Channel::C4).unwrap()
+    );
+    destroy(pwm);
+}
+
+macro_rules! invert_test {
+    ($name:ident, $read:expr, $written:expr) => {
+        #[test]
+        fn $name() {
+            let mut written = vec![Register::C2_ON_L];
+            written.extend_from_slice(&$written);
+            let trans = [
+                I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+                I2cTrans::write_read(DEV_ADDR, vec![Register::C2_ON_L], $read.to_vec()),
+                I2cTrans::write(DEV_ADDR, written),
+            ];
+            let mut pwm = new(&trans);
+            pwm.invert_channel(Channel::C2).unwrap();
+            destroy(pwm);
+        }
+    };
+}
+
+invert_test!(can_invert_channel, [0, 0, 0xE8, 0x03], [0, 0, 0x17, 0x0C]);
+invert_test!(
+    can_invert_full_off_channel,
+    [0, 0, 0, 0x10],
+    [0, 0x10, 0, 0]
+);
+invert_test!(can_invert_full_on_channel, [0, 0x10, 0, 0], [0, 0, 0, 0x10]);
+
+invalid_test!(cannot_invert_all_channels, invert_channel, Channel::All);
+
+#[test]
+fn can_read_channel_registers() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::C2_ON_L],
+            vec![0x34, 0x12, 0x78, 0x06],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    let regs = pwm.get_channel_registers(Channel::C2).unwrap();
+    assert_eq!(ChannelRegisters::from_bytes([0x34, 0x12, 0x78, 0x06]), regs);
+    assert_eq!(0x234, regs.on());
+    assert!(regs.full_on());
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_get_all_channel_registers,
+    get_channel_registers,
+    Channel::All
+);
+
+#[test]
+fn can_get_channel_raw() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::C2_ON_L],
+            vec![0x34, 0x12, 0x78, 0x06],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    let (on, off) = pwm.get_channel_raw(Channel::C2).unwrap();
+    assert_eq!(
+        RawCounter {
+            count: 0x234,
+            full: true
+        },
+        on
+    );
+    assert_eq!(
+        RawCounter {
+            count: 0x678,
+            full: false
+        },
+        off
+    );
+    destroy(pwm);
+}
+
+invalid_test!(cannot_get_all_channel_raw, get_channel_raw, Channel::All);
+
+#[test]
+fn can_read_channels_into() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::C14_ON_L],
+            vec![1, 0, 2, 0x10, 3, 0x11, 4, 0x0F],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    let mut out = [(0, 0); 2];
+    pwm.read_channels_into(Channel::C14, &mut out).unwrap();
+    assert_eq!([(1, 0x1002), (0x1103, 0xF04)], out);
+    destroy(pwm);
+}
+
+#[test]
+fn read_channels_into_empty_buffer_does_nothing() {
+    let mut pwm = new(&[]);
+    pwm.read_channels_into(Channel::C3, &mut []).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_read_channels_into_past_last_channel() {
+    let mut pwm = new(&[]);
+    let mut out = [(0, 0); 3];
+    assert_invalid_input_data(pwm.read_channels_into(Channel::C14, &mut out));
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_read_channels_into_from_all() {
+    let mut pwm = new(&[]);
+    let mut out = [(0, 0); 1];
+    assert_invalid_input_data(pwm.read_channels_into(Channel::All, &mut out));
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_channel_on_value_with_flag_bit,
+    set_channel_on,
+    Channel::C0,
+    0x1FFF
+);
+
+invalid_test!(
+    cannot_set_channel_off_value_with_flag_bit,
+    set_channel_off,
+    Channel::C0,
+    0x1000
+);
+
+invalid_test!(
+    cannot_set_channel_off_value_with_reserved_bits,
+    set_channel_off,
+    Channel::C0,
+    0xE000
+);
+
+#[test]
+fn set_channel_on_preserves_full_on_flag() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_ON_L + 1], vec![0b0001_0101]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_ON_L, 0xFF, 0b0001_1111]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on(Channel::C3, 4095).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_channel_off_preserves_full_off_flag() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0b0001_1111]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0b0001_0001]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_channel_off_max_value_does_not_set_flag() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0b0000_0000]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0xFF, 0b0000_1111]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_off(Channel::C3, 4095).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_channel_on_clears_reserved_bits() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_ON_L + 1], vec![0b1110_0000]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_ON_L, 0x01, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on(Channel::C3, 1).unwrap();
+    destroy(pwm);
+}
+
+macro_rules! duty_cycle_test {
+    ($name:ident, $channel:ident, $duty:expr, $reg:ident, $($data:expr),*) => {
+        #[test]
+        fn $name() {
+            let trans = [
+                I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+                I2cTrans::write(DEV_ADDR, vec![Register::$reg, $($data),*]),
+            ];
+            let mut pwm = new(&trans);
+            pwm.set_channel_duty_cycle(Channel::$channel, $duty).unwrap();
+            destroy(pwm);
+        }
+    };
+}
+
+duty_cycle_test!(duty_cycle_zero_is_full_off, C1, 0.0, C1_ON_L, 0, 0, 0, 0x10);
+duty_cycle_test!(duty_cycle_one_is_full_on, C1, 1.0, C1_ON_L, 0, 0x10, 0, 0);
+duty_cycle_test!(duty_cycle_half, C1, 0.5, C1_ON_L, 0, 0, 0, 0x08);
+duty_cycle_test!(duty_cycle_quarter, C15, 0.25, C15_ON_L, 0, 0, 0, 0x04);
+duty_cycle_test!(duty_cycle_rounds_to_off, C0, 0.0001, C0_ON_L, 0, 0, 0, 0x10);
+duty_cycle_test!(duty_cycle_tiny_is_one_tick, C0, 0.0002, C0_ON_L, 0, 0, 1, 0);
+duty_cycle_test!(duty_cycle_almost_one, C0, 0.9998, C0_ON_L, 0, 0, 0xFF, 0x0F);
+duty_cycle_test!(duty_cycle_all_half, All, 0.5, ALL_C_ON_L, 0, 0, 0, 0x08);
+duty_cycle_test!(duty_cycle_all_zero, All, 0.0, ALL_C_ON_L, 0, 0, 0, 0x10);
+duty_cycle_test!(duty_cycle_all_one, All, 1.0, ALL_C_ON_L, 0, 0x10, 0, 0);
+
+invalid_test!(
+    cannot_set_duty_cycle_negative,
+    set_channel_duty_cycle,
+    Channel::C0,
+    -0.1
+);
+invalid_test!(
+    cannot_set_duty_cycle_too_big,
+    set_channel_duty_cycle,
+    Channel::C0,
+    1.1
+);
+invalid_test!(
+    cannot_set_duty_cycle_nan,
+    set_channel_duty_cycle,
+    Channel::C0,
+    f32::NAN
+);
+
+macro_rules! dithered_test {
+    ($name:ident, $duty:expr, $frame:expr, $($data:expr),*) => {
+        #[test]
+        fn $name() {
+            let trans = [
+                I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+                I2cTrans::write(DEV_ADDR, vec![Register::C1_ON_L, $($data),*]),
+            ];
+            let mut pwm = new(&trans);
+            pwm.set_channel_duty_dithered(Channel::C1, $duty, $frame)
+                .unwrap();
+            destroy(pwm);
+        }
+    };
+}
+
+// 100.25 ticks: one frame out of four is one tick longer
+dithered_test!(dithered_short_frame, 100.25 / 4096.0, 0, 0, 0, 100, 0);
+dithered_test!(dithered_long_frame, 100.25 / 4096.0, 3, 0, 0, 101, 0);
+dithered_test!(dithered_frame_wraps, 100.25 / 4096.0, 19, 0, 0, 101, 0);
+dithered_test!(dithered_exact_value, 100.0 / 4096.0, 3, 0, 0, 100, 0);
+dithered_test!(dithered_zero_is_full_off, 0.0, 7, 0, 0, 0, 0x10);
+dithered_test!(dithered_one_is_full_on, 1.0, 7, 0, 0x10, 0, 0);
+
+#[test]
+fn dithered_duty_cycle_averages_over_frames() {
+    let mut trans = vec![I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI])];
+    // 100.625 ticks: 10 out of 16 frames are one tick longer
+    for frame in 0..16 {
+        let long = [1, 3, 4, 6, 7, 9, 11, 12, 14, 15].contains(&frame);
+        let off = if long { 101 } else { 100 };
+        trans.push(I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C1_ON_L, 0, 0, off, 0],
+        ));
+    }
+    let mut pwm = new(&trans);
+    for frame in 0..16 {
+        pwm.set_channel_duty_dithered(Channel::C1, 100.625 / 4096.0, frame)
+            .unwrap();
+    }
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_dithered_duty_cycle_too_big,
+    set_channel_duty_dithered,
+    Channel::C0,
+    1.1,
+    0
+);
+invalid_test!(
+    cannot_set_dithered_duty_cycle_nan,
+    set_channel_duty_dithered,
+    Channel::C0,
+    f32::NAN,
+    0
+);
+
+#[test]
+fn handle_uses_its_channel() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C5_ON_L, 0, 0, 0, 0x08]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C5_ON_L, 2, 1, 4, 3]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C5_ON_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C5_ON_L + 1, 0x10]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C5_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C5_OFF_L + 1, 0x10]),
+    ];
+    let mut pwm = new(&trans);
+    let mut handle = pwm.handle(Channel::C5);
+    assert_eq!(Channel::C5, handle.channel());
+    handle.set_duty(0.5).unwrap();
+    handle.set_on_off(0x102, 0x304).unwrap();
+    handle.full_on(true).unwrap();
+    handle.full_off(true).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn split_pins_write_their_channels() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0, 0, 0x08]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C15_ON_L, 0, 0x10, 0, 0]),
+    ];
+    let pwm = RefCell::new(new(&trans));
+    {
+        let mut pins = Pca9685::split(&pwm);
+        assert_eq!(Channel::C2, pins[2].channel());
+        assert_eq!(4096, pins[2].get_max_duty());
+        pins[2].set_duty(2048);
+        assert_eq!(2048, pins[2].get_duty());
+        pins[15].set_duty(5000);
+        assert_eq!(4096, pins[15].get_duty());
+    }
+    destroy(pwm.into_inner());
+}
+
+#[test]
+fn split_pin_restores_duty_when_enabled() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_ON_L, 0, 0, 0, 0x04]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0x04]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L + 1, 0x14]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_ON_L, 0, 0, 0, 0x08]),
+    ];
+    let pwm = RefCell::new(new(&trans));
+    {
+        let mut pins = Pca9685::split(&pwm);
+        pins[3].set_duty(1024);
+        pins[3].disable();
+        // Only stored while disabled.
+        pins[3].set_duty(2048);
+        pins[3].enable();
+    }
+    destroy(pwm.into_inner());
+}
+
+#[test]
+fn split_pin_latches_bus_errors() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 0, 0x08])
+            .with_error(MockError::Io(ErrorKind::Other)),
+    ];
+    let pwm = RefCell::new(new(&trans));
+    {
+        let mut pins = Pca9685::split(&pwm);
+        pins[0].set_duty(2048);
+    }
+    assert!(pwm.borrow().bus_error_occurred());
+    destroy(pwm.into_inner());
+}
+
+const REVERSED: [u8; 16] = [15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0];
+
+invalid_test!(
+    cannot_set_channel_mapping_out_of_range,
+    set_channel_mapping,
+    [16, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+
+invalid_test!(
+    cannot_set_channel_mapping_duplicated,
+    set_channel_mapping,
+    [0, 0, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+);
+
+#[test]
+fn channel_mapping_is_used_for_single_channel() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C15_ON_L, 2, 1, 4, 3]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C13_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C13_OFF_L + 1, 0x10]),
+        I2cTrans::write(DEV_ADDR, vec![Register::ALL_C_ON_L, 0, 0, 0, 0x08]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_mapping(REVERSED).unwrap();
+    pwm.set_channel_on_off(Channel::C0, 0x102, 0x304).unwrap();
+    pwm.set_channel_full_off(Channel::C2, true).unwrap();
+    pwm.set_channel_duty_cycle(Channel::All, 0.5).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn channel_mapping_is_used_for_all_on_off() {
+    let mut data = vec![0; 65];
+    data[0] = Register::C0_ON_L;
+    // logical channel 0 is written to device channel 15
+    data[61] = 1;
+    data[63] = 2;
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, data),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_mapping(REVERSED).unwrap();
+    let mut on = [0; 16];
+    let mut off = [0; 16];
+    on[0] = 1;
+    off[0] = 2;
+    pwm.set_all_on_off(&on, &off).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn channel_mapping_is_used_for_reading() {
+    let mut data = vec![0; 64];
+    // device channel 14
+    data[56] = 1;
+    data[58] = 2;
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data.clone()),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_mapping(REVERSED).unwrap();
+    let values = pwm.get_all_channels_on_off_with_flags().unwrap();
+    assert_eq!((1, 2), (values[2], values[3]));
+    let mut out = [(0, 0); 2];
+    pwm.read_channels_into(Channel::C0, &mut out).unwrap();
+    assert_eq!([(0, 0), (1, 2)], out);
+    destroy(pwm);
+}
+
+#[test]
+fn identity_channel_mapping_reads_range() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C1_ON_L], vec![1, 0, 2, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_mapping(REVERSED).unwrap();
+    pwm.set_channel_mapping([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15])
+        .unwrap();
+    let mut out = [(0, 0); 1];
+    pwm.read_channels_into(Channel::C1, &mut out).unwrap();
+    assert_eq!([(1, 2)], out);
+    destroy(pwm);
+}
+
+invalid_test!(
+    cannot_set_on_offset_too_big,
+    set_channel_on_offset,
+    Channel::C0,
+    4096
+);
+
+#[test]
+fn duty_cycle_applies_on_offset() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 1, 0, 9]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_ON_L, 0, 0, 0, 8]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_offset(Channel::C2, 0x100).unwrap();
+    pwm.set_channel_duty_cycle(Channel::C2, 0.5).unwrap();
+    pwm.set_channel_duty_cycle(Channel::C3, 0.5).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn duty_cycle_with_on_offset_wraps_off_counter() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0x0C, 0, 0x04]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_offset(Channel::C2, 0xC00).unwrap();
+    pwm.set_channel_duty_cycle(Channel::C2, 0.5).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn duty_cycle_extremes_ignore_on_offset() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0x10, 0, 0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0, 0, 0x10]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_offset(Channel::C2, 0x100).unwrap();
+    pwm.set_channel_duty_cycle(Channel::C2, 1.0).unwrap();
+    pwm.set_channel_duty_cycle(Channel::C2, 0.0).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn duty_cycle_all_uses_common_on_offset() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::ALL_C_ON_L, 0, 1, 0, 9]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_offset(Channel::All, 0x100).unwrap();
+    pwm.set_channel_duty_cycle(Channel::All, 0.5).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn duty_cycle_all_ignores_different_on_offsets() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::ALL_C_ON_L, 0, 0, 0, 8]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_offset(Channel::C0, 0x100).unwrap();
+    pwm.set_channel_duty_cycle(Channel::All, 0.5).unwrap();
+    destroy(pwm);
+}
+
+macro_rules! window_test {
+    ($name:ident, $on:expr, $off:expr, $($data:expr),*) => {
+        #[test]
+        fn $name() {
+            let trans = [
+                I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+                I2cTrans::write(DEV_ADDR, vec![Register::C4_ON_L, $($data),*]),
+            ];
+            let mut pwm = new(&trans);
+            pwm.set_channel_window(Channel::C4, $on, $off).unwrap();
+            destroy(pwm);
+        }
+    };
+}
+
+window_test!(can_set_window, 0.25, 0.75, 0, 4, 0, 0x0C);
+window_test!(can_set_wrapping_window, 0.75, 0.25, 0, 0x0C, 0, 4);
+window_test!(window_rounds_to_start_of_period, 0.99995, 0.5, 0, 0, 0, 8);
+
+invalid_test!(
+    cannot_set_window_full_period,
+    set_channel_window,
+    Channel::C0,
+    0.0,
+    1.0
+);
+invalid_test!(
+    cannot_set_window_negative,
+    set_channel_window,
+    Channel::C0,
+    -0.1,
+    0.5
+);
+invalid_test!(
+    cannot_set_window_nan,
+    set_channel_window,
+    Channel::C0,
+    0.5,
+    f32::NAN
+);
+invalid_test!(
+    cannot_set_empty_window,
+    set_channel_window,
+    Channel::C0,
+    0.5,
+    0.5
+);
+
+#[test]
+fn dedup_skips_unchanged_channel_off() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0x01]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x03, 0x01]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_dedup(true);
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    pwm.set_channel_off(Channel::C3, 0x103).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn dedup_is_disabled_by_default() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0x01]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn dedup_forgets_value_written_by_other_methods() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_ON_L, 0, 0, 0x03, 0x01]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0x01]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_dedup(true);
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    pwm.set_channel_on_off(Channel::C3, 0, 0x103).unwrap();
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn dedup_keeps_value_after_prescale_change() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 100]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_dedup(true);
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    pwm.set_prescale(100).unwrap();
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn dedup_forgets_value_after_failed_write() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::ALL_C_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::ALL_C_OFF_L + 1, 0x10])
+            .with_error(MockError::Io(ErrorKind::Other)),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0x01]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_dedup(true);
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    pwm.set_channel_full_off(Channel::All, true).unwrap_err();
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn dedup_forgets_value_after_failed_bulk_write() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C2_ON_L, 0, 0, 0, 0, 0, 0, 0x02, 0x01],
+        )
+        .with_error(MockError::Io(ErrorKind::Other)),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C3_OFF_L + 1], vec![0x01]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C3_OFF_L, 0x02, 0x01]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_dedup(true);
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    pwm.write_channels_from(Channel::C2, &[(0, 0), (0, 0x102)])
+        .unwrap_err();
+    pwm.set_channel_off(Channel::C3, 0x102).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_mute_and_unmute_channel() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::C2_ON_L],
+            vec![0x02, 0x01, 0x04, 0x03],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_OFF_L + 1, 0x13]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0x02, 0x01, 0x04, 0x03]),
+    ];
+    let mut pwm = new(&trans);
+    assert!(!pwm.is_channel_muted(Channel::C2));
+    pwm.mute_channel(Channel::C2).unwrap();
+    assert!(pwm.is_channel_muted(Channel::C2));
+    pwm.unmute_channel(Channel::C2).unwrap();
+    assert!(!pwm.is_channel_muted(Channel::C2));
+    destroy(pwm);
+}
+
+#[test]
+fn unmute_restores_full_off_flag() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C2_ON_L], vec![0, 0, 0, 0x10]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_OFF_L + 1, 0x10]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0, 0, 0x10]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.mute_channel(Channel::C2).unwrap();
+    pwm.unmute_channel(Channel::C2).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn muting_twice_keeps_values() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C2_ON_L], vec![0, 0, 0xFF, 0x07]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_OFF_L + 1, 0x17]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 0, 0, 0xFF, 0x07]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.mute_channel(Channel::C2).unwrap();
+    pwm.mute_channel(Channel::C2).unwrap();
+    pwm.unmute_channel(Channel::C2).unwrap();
+    // not muted anymore
+    pwm.unmute_channel(Channel::C2).unwrap();
+    destroy(pwm);
+}
+
+invalid_test!(cannot_mute_all, mute_channel, Channel::All);
+invalid_test!(cannot_unmute_all, unmute_channel, Channel::All);
+
+#[test]
+fn pulse_from_raw_considers_flags() {
+    assert_eq!(PulseResult::Length(0), pulse_from_raw(0, 0));
+    assert_eq!(PulseResult::Length(2048), pulse_from_raw(1024, 3072));
+    assert_eq!(PulseResult::Length(4095), pulse_from_raw(1, 0));
+    assert_eq!(PulseResult::Full, pulse_from_raw(0x1000, 0));
+    assert_eq!(PulseResult::Off, pulse_from_raw(0, 0x1000));
+    assert_eq!(PulseResult::Off, pulse_from_raw(0x1000, 0x1FFF));
+}
+
+#[test]
+fn pulse_from_raw_wraps_around() {
+    assert_eq!(PulseResult::Length(2000), pulse_from_raw(3000, 904));
+    assert_eq!(PulseResult::Length(1), pulse_from_raw(4095, 0));
+}
+
+#[test]
+fn can_get_effective_pulse_wrapping_around() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C5_ON_L], vec![0, 0x0F, 0, 0x01]),
+    ];
+    let mut pwm = new(&trans);
+    // 4096 - 0xF00 + 0x100
+    assert_eq!(0x200, pwm.get_effective_pulse(Channel::C5).unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn strict_sleep_rejects_channel_writes_while_asleep() {
+    let trans = [I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI])];
+    let mut pwm = new(&trans);
+    pwm.set_strict_sleep(true);
+    assert_error!(
+        pwm.set_channel_on_off(Channel::C0, 0, 1024),
+        Error::DeviceAsleep
+    );
+    assert_error!(
+        pwm.set_channel_simple(Channel::All, 1024),
+        Error::DeviceAsleep
+    );
+    assert_error!(
+        pwm.write_channels_from(Channel::C3, &[(0, 1024)]),
+        Error::DeviceAsleep
+    );
+    destroy(pwm);
+}
+
+#[test]
+fn strict_sleep_accepts_channel_writes_while_awake() {
+    let trans = [
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT & !BitFlags::SLEEP],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI & !BitFlags::SLEEP]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 0, 4]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_strict_sleep(true);
+    pwm.enable().unwrap();
+    pwm.set_channel_on_off(Channel::C0, 0, 1024).unwrap();
+    pwm.disable().unwrap();
+    assert_error!(
+        pwm.set_channel_on_off(Channel::C0, 0, 1024),
+        Error::DeviceAsleep
+    );
+    destroy(pwm);
+}
+
+#[test]
+fn channel_writes_while_asleep_are_accepted_by_default() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 0, 4]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_off(Channel::C0, 0, 1024).unwrap();
+    destroy(pwm);
+}
@@ -0,0 +1,50 @@
+use pwm_pca9685::FrameFade;
+
+fn frame(off: u16) -> [u16; 32] {
+    let mut frame = [0; 32];
+    for value in frame.iter_mut().skip(1).step_by(2) {
+        *value = off;
+    }
+    frame
+}
+
+#[test]
+fn fade_interpolates_and_ends_at_target() {
+    let mut fade = FrameFade::new(frame(100), frame(200), 3);
+    assert_eq!(3, fade.remaining());
+    // 133.3 and 166.7 are rounded to the nearest value.
+    assert_eq!(Some(frame(133)), fade.next_frame());
+    assert_eq!(Some(frame(167)), fade.next_frame());
+    assert_eq!(Some(frame(200)), fade.next_frame());
+    assert_eq!(0, fade.remaining());
+    assert_eq!(None, fade.next_frame());
+}
+
+#[test]
+fn fade_can_go_down() {
+    let frames: Vec<_> = FrameFade::new(frame(4095), frame(0), 4).collect();
+    assert_eq!(
+        vec![frame(3071), frame(2048), frame(1024), frame(0)],
+        frames
+    );
+}
+
+#[test]
+fn fade_clears_flags_until_last_frame() {
+    let mut from = frame(0);
+    from[7] = 0x1000; // C3 full OFF
+    let mut to = frame(0x800);
+    to[8] = 0x1000; // C4 full ON
+    let mut fade = FrameFade::new(from, to, 2);
+    let mut expected = frame(0x400);
+    expected[8] = 0;
+    assert_eq!(Some(expected), fade.next_frame());
+    assert_eq!(Some(to), fade.next_frame());
+}
+
+#[test]
+fn zero_steps_returns_target() {
+    let mut fade = FrameFade::new(frame(100), frame(200), 0);
+    assert_eq!(Some(frame(200)), fade.next_frame());
+    assert_eq!(None, fade.next_frame());
+}
@@ -0,0 +1,150 @@
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use pwm_pca9685::{Channel, RgbwFixture};
+
+mod common;
+use crate::common::{assert_invalid_input_data, destroy, new, Register, DEV_ADDR, MODE1_AI};
+
+#[test]
+fn consecutive_channels_are_written_at_once() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Register::C4_ON_L,
+                0,
+                0x10,
+                0,
+                0,
+                0,
+                0,
+                0x08,
+                0x08,
+                0,
+                0,
+                0,
+                0x10,
+                0,
+                0,
+                0x10,
+                0,
+            ],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    let fixture = RgbwFixture::new(Channel::C4, Channel::C5, Channel::C6, Channel::C7);
+    fixture.set_color(&mut pwm, (255, 128, 0, 1)).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn separate_channels_are_grouped() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C0_ON_L, 0, 0, 0, 0x10, 0, 0x10, 0, 0],
+        ),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C8_ON_L, 0, 0, 0x08, 0x08, 0, 0, 0x10, 0],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    let fixture = RgbwFixture::new(Channel::C8, Channel::C1, Channel::C9, Channel::C0);
+    fixture.set_color(&mut pwm, (128, 255, 1, 0)).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn gamma_is_applied() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Register::C0_ON_L,
+                0,
+                0,
+                0x83,
+                0x03,
+                0,
+                0,
+                0,
+                0x10,
+                0,
+                0,
+                0,
+                0x10,
+                0,
+                0,
+                0,
+                0x10,
+            ],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    let fixture =
+        RgbwFixture::new(Channel::C0, Channel::C1, Channel::C2, Channel::C3).with_gamma(2.2);
+    fixture.set_color(&mut pwm, (128, 0, 0, 0)).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn on_offsets_are_applied() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Register::C0_ON_L,
+                0,
+                0x01,
+                0x08,
+                0x09,
+                0,
+                0,
+                0,
+                0x10,
+                0,
+                0,
+                0,
+                0x10,
+                0,
+                0,
+                0,
+                0x10,
+            ],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_on_offset(Channel::C0, 0x100).unwrap();
+    let fixture = RgbwFixture::new(Channel::C0, Channel::C1, Channel::C2, Channel::C3);
+    fixture.set_color(&mut pwm, (128, 0, 0, 0)).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_use_all_channels() {
+    let mut pwm = new(&[]);
+    let fixture = RgbwFixture::new(Channel::C0, Channel::All, Channel::C2, Channel::C3);
+    assert_invalid_input_data(fixture.set_color(&mut pwm, (0, 0, 0, 0)));
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_use_channel_twice() {
+    let mut pwm = new(&[]);
+    let fixture = RgbwFixture::new(Channel::C0, Channel::C1, Channel::C0, Channel::C3);
+    assert_invalid_input_data(fixture.set_color(&mut pwm, (0, 0, 0, 0)));
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_use_invalid_gamma() {
+    let mut pwm = new(&[]);
+    let fixture =
+        RgbwFixture::new(Channel::C0, Channel::C1, Channel::C2, Channel::C3).with_gamma(-1.0);
+    assert_invalid_input_data(fixture.set_color(&mut pwm, (0, 0, 0, 0)));
+    destroy(pwm);
+}
@@ -0,0 +1,345 @@
+use embedded_hal::blocking::i2c;
+use embedded_hal_mock::{i2c::Transaction as I2cTrans, MockError};
+use pwm_pca9685::{diff_frames, nb, Address, Channel, Error, FrameWriter, Pca9685};
+use std::{cell::Cell, io::ErrorKind};
+
+mod common;
+use crate::common::{destroy, new, Register, DEV_ADDR, MODE1_AI};
+
+fn chunk(first_channel: u8, channels: u8) -> I2cTrans {
+    let mut data = vec![Register::C0_ON_L + first_channel * 4];
+    for _ in 0..channels {
+        data.extend_from_slice(&[0, 0, 0xFF, 0x07]);
+    }
+    I2cTrans::write(DEV_ADDR, data)
+}
+
+#[test]
+fn writes_frame_in_chunks() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        chunk(0, 6),
+        chunk(6, 6),
+        chunk(12, 4),
+    ];
+    let mut pwm = new(&trans);
+    let mut writer = FrameWriter::new([(0, 2047); 16], 6);
+    assert!(matches!(writer.poll(&mut pwm), Err(nb::Error::WouldBlock)));
+    assert!(matches!(writer.poll(&mut pwm), Err(nb::Error::WouldBlock)));
+    assert!(!writer.is_done());
+    writer.poll(&mut pwm).unwrap();
+    assert!(writer.is_done());
+    // nothing else is written
+    writer.poll(&mut pwm).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn writes_whole_frame_at_once() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        chunk(0, 16),
+    ];
+    let mut pwm = new(&trans);
+    let mut writer = FrameWriter::new([(0, 2047); 16], 16);
+    writer.poll(&mut pwm).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn writes_at_least_one_channel() {
+    let mut trans = vec![I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI])];
+    for i in 0..16 {
+        trans.push(chunk(i, 1));
+    }
+    let mut pwm = new(&trans);
+    let mut writer = FrameWriter::new([(0, 2047); 16], 0);
+    nb::block!(writer.poll(&mut pwm)).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn retries_chunk_after_error() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        chunk(0, 8).with_error(MockError::Io(ErrorKind::Other)),
+        chunk(0, 8),
+        chunk(8, 8),
+    ];
+    let mut pwm = new(&trans);
+    let mut writer = FrameWriter::new([(0, 2047); 16], 8);
+    assert!(matches!(writer.poll(&mut pwm), Err(nb::Error::Other(_))));
+    nb::block!(writer.poll(&mut pwm)).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn equal_frames_have_no_changes() {
+    let frame = [0x123; 32];
+    assert_eq!(0, diff_frames(&frame, &frame));
+}
+
+#[test]
+fn on_and_off_changes_are_detected() {
+    let prev = [0; 32];
+    let mut next = [0; 32];
+    next[0] = 1; // C0 ON
+    next[5] = 0x1000; // C2 OFF
+    next[31] = 4095; // C15 OFF
+    assert_eq!(0b1000_0000_0000_0101, diff_frames(&prev, &next));
+}
+
+/// Expected write of the channels `first..=last` of `frame`.
+fn frame_write(frame: &[u16; 32], first: usize, last: usize) -> I2cTrans {
+    let mut data = vec![Register::C0_ON_L + first as u8 * 4];
+    for value in &frame[first * 2..(last + 1) * 2] {
+        data.extend_from_slice(&[*value as u8, (*value >> 8) as u8]);
+    }
+    I2cTrans::write(DEV_ADDR, data)
+}
+
+fn test_frame() -> [u16; 32] {
+    let mut frame = [0; 32];
+    for (i, value) in frame.iter_mut().enumerate() {
+        *value = i as u16 * 100;
+    }
+    frame
+}
+
+#[test]
+fn push_frame_writes_all_channels() {
+    let frame = test_frame();
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        frame_write(&frame, 0, 15),
+        frame_write(&frame, 0, 15),
+    ];
+    let mut pwm = new(&trans);
+    pwm.push_frame(&frame).unwrap();
+    // without deduplication the frame is always written
+    pwm.push_frame(&frame).unwrap();
+    destroy(pwm);
+}
+
+/// Read back of all channels returning `frame`.
+fn frame_read(frame: &[u16; 32]) -> I2cTrans {
+    let mut data = vec![];
+    for value in frame {
+        data.extend_from_slice(&[*value as u8, (*value >> 8) as u8]);
+    }
+    I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], data)
+}
+
+#[test]
+fn push_frame_verified_reads_frame_back() {
+    let frame = test_frame();
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        frame_write(&frame, 0, 15),
+        frame_read(&frame),
+    ];
+    let mut pwm = new(&trans);
+    pwm.push_frame_verified(&frame).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn push_frame_verified_reports_first_mismatch() {
+    let frame = test_frame();
+    let mut corrupted = frame;
+    corrupted[7] ^= 0x10; // C3 OFF
+    corrupted[30] ^= 0x01; // C15 ON
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        frame_write(&frame, 0, 15),
+        frame_read(&corrupted),
+        // The frame is written completely again.
+        frame_write(&frame, 0, 15),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_dedup(true);
+    assert_eq!(
+        Err(Error::VerificationFailed(Some(Channel::C3))),
+        pwm.push_frame_verified(&frame)
+    );
+    pwm.push_frame(&frame).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn push_frame_verified_masks_flags() {
+    let frame = test_frame();
+    let mut read = frame;
+    read[4] |= 0x1000; // C2 ON
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        frame_write(&frame, 0, 15),
+        frame_read(&read),
+    ];
+    let mut pwm = new(&trans);
+    pwm.push_frame_verified(&frame).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn push_frame_ignores_bits_above_flag() {
+    let mut frame = [0x1FFF; 32];
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        frame_write(&frame, 0, 15),
+    ];
+    frame[7] = 0xFFFF;
+    let mut pwm = new(&trans);
+    pwm.push_frame(&frame).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn push_frame_uses_channel_mapping() {
+    let mut frame = [0; 32];
+    frame[1] = 0x123; // C0 OFF
+    let mut expected = [0; 32];
+    expected[31] = 0x123; // C15 OFF
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        frame_write(&expected, 0, 15),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_mapping([15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0])
+        .unwrap();
+    pwm.push_frame(&frame).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn push_frame_with_dedup_writes_changed_range() {
+    let frame = test_frame();
+    let mut next = frame;
+    next[7] = 1; // C3 OFF
+    next[10] = 2; // C5 ON
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        frame_write(&frame, 0, 15),
+        frame_write(&next, 3, 5),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_dedup(true);
+    pwm.push_frame(&frame).unwrap();
+    pwm.push_frame(&frame).unwrap();
+    pwm.push_frame(&next).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn push_frame_forgets_frame_after_other_channel_write() {
+    let frame = test_frame();
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        frame_write(&frame, 0, 15),
+        I2cTrans::write(DEV_ADDR, vec![Register::C9_ON_L, 0, 0, 0, 0]),
+        frame_write(&frame, 0, 15),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_dedup(true);
+    pwm.push_frame(&frame).unwrap();
+    pwm.set_channel_on_off(Channel::C9, 0, 0).unwrap();
+    pwm.push_frame(&frame).unwrap();
+    destroy(pwm);
+}
+
+/// Counts the transfers and the bytes written to the bus.
+struct ByteCounter<'a> {
+    written: &'a Cell<usize>,
+    transfers: &'a Cell<usize>,
+}
+
+impl<'a> ByteCounter<'a> {
+    fn new(written: &'a Cell<usize>, transfers: &'a Cell<usize>) -> Self {
+        ByteCounter { written, transfers }
+    }
+
+    fn count(&self, bytes: &[u8]) {
+        self.written.set(self.written.get() + bytes.len());
+        self.transfers.set(self.transfers.get() + 1);
+    }
+}
+
+impl i2c::Write for ByteCounter<'_> {
+    type Error = ();
+    fn write(&mut self, _address: u8, bytes: &[u8]) -> Result<(), ()> {
+        self.count(bytes);
+        Ok(())
+    }
+}
+
+impl i2c::WriteRead for ByteCounter<'_> {
+    type Error = ();
+    fn write_read(&mut self, _address: u8, bytes: &[u8], _buffer: &mut [u8]) -> Result<(), ()> {
+        self.count(bytes);
+        Ok(())
+    }
+}
+
+#[test]
+fn push_frame_bytes_per_frame() {
+    let (written, transfers) = (Cell::new(0), Cell::new(0));
+    let mut pwm = Pca9685::new(ByteCounter::new(&written, &transfers), Address::default()).unwrap();
+    let mut bytes_for_frame = |frame: &[u16; 32]| {
+        written.set(0);
+        pwm.push_frame(frame).unwrap();
+        written.get()
+    };
+    let frame = test_frame();
+    // Auto-increment is enabled once with a 2-byte write.
+    assert_eq!(2 + 65, bytes_for_frame(&frame));
+    assert_eq!(65, bytes_for_frame(&frame));
+
+    pwm.set_dedup(true);
+    let mut bytes_for_frame = |frame: &[u16; 32]| {
+        written.set(0);
+        pwm.push_frame(frame).unwrap();
+        written.get()
+    };
+    assert_eq!(65, bytes_for_frame(&frame));
+    assert_eq!(0, bytes_for_frame(&frame));
+    let mut next = frame;
+    next[31] = 0;
+    assert_eq!(5, bytes_for_frame(&next));
+}
+
+#[test]
+fn reported_write_bytes_match_transfers() {
+    let (written, transfers) = (Cell::new(0), Cell::new(0));
+    let mut pwm = Pca9685::new(ByteCounter::new(&written, &transfers), Address::default()).unwrap();
+    pwm.push_frame(&test_frame()).unwrap();
+    assert_eq!(2 + Pca9685::frame_bytes(), written.get());
+    written.set(0);
+    pwm.set_channel_on_off(Channel::C3, 0, 1024).unwrap();
+    assert_eq!(Pca9685::channels_write_bytes(1), written.get());
+    written.set(0);
+    pwm.write_channels_from(Channel::C3, &[(0, 1024); 5])
+        .unwrap();
+    assert_eq!(Pca9685::channels_write_bytes(5), written.get());
+}
+
+#[test]
+fn bulk_writes_enable_auto_increment_once() {
+    let (written, transfers) = (Cell::new(0), Cell::new(0));
+    let mut pwm = Pca9685::new(ByteCounter::new(&written, &transfers), Address::default()).unwrap();
+    let frame = test_frame();
+    for _ in 0..10 {
+        pwm.push_frame(&frame).unwrap();
+        pwm.write_channels_from(Channel::C0, &[(0, 1024); 16])
+            .unwrap();
+        pwm.get_all_channels_on_off_with_flags().unwrap();
+    }
+    // Auto-increment is enabled with one additional transfer.
+    assert_eq!(1 + 30, transfers.get());
+
+    // The cached state is forgotten with the rest of the driver state.
+    pwm.reset_internal_driver_state();
+    transfers.set(0);
+    pwm.push_frame(&frame).unwrap();
+    pwm.push_frame(&frame).unwrap();
+    assert_eq!(1 + 2, transfers.get());
+}
@@ -0,0 +1,437 @@
+use embedded_hal_mock::{
+    delay::MockNoop as DelayMock,
+    i2c::{Mock as I2cMock, Transaction as I2cTrans},
+    MockError,
+};
+use pwm_pca9685::{Address, Channel, Error, Pca9685};
+use std::io::ErrorKind;
+
+mod common;
+use crate::common::{
+    assert_invalid_input_data, destroy, new, BitFlags, Register, DEV_ADDR, MODE1_AI, MODE1_DEFAULT,
+};
+
+#[test]
+fn can_compute_frequency_error() {
+    let pwm = new(&[]);
+    // prescale 121 -> 50.0288 Hz
+    let error = pwm.frequency_error(50.0).unwrap();
+    assert!((error - 0.000_576).abs() < 0.000_01);
+    destroy(pwm);
+}
+
+#[test]
+fn frequency_error_is_signed() {
+    let pwm = new(&[]);
+    // prescale 100 -> 60.4 Hz
+    assert!(pwm.frequency_error(60.5).unwrap() < 0.0);
+    assert!(pwm.frequency_error(60.3).unwrap() > 0.0);
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_compute_frequency_error_out_of_range() {
+    let pwm = new(&[]);
+    assert_invalid_input_data(pwm.frequency_error(2000.0));
+    assert_invalid_input_data(pwm.frequency_error(10.0));
+    destroy(pwm);
+}
+
+#[test]
+fn default_pulse_resolution() {
+    let pwm = new(&[]);
+    // prescale 30 -> 31 * 4096 / 25 MHz per period
+    assert!((pwm.pulse_resolution_us() - 1.24).abs() < 0.001);
+    destroy(pwm);
+}
+
+#[test]
+fn pulse_resolution_follows_prescale() {
+    let trans = [I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121])];
+    let mut pwm = new(&trans);
+    pwm.set_prescale(121).unwrap();
+    // about 50 Hz
+    assert!((pwm.pulse_resolution_us() - 4.88).abs() < 0.001);
+    destroy(pwm);
+}
+
+#[test]
+fn can_compute_phase_counts() {
+    let trans = [I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121])];
+    let mut pwm = new(&trans);
+    pwm.set_prescale(121).unwrap();
+    // 4.88 us per tick
+    assert_eq!(0, pwm.phase_counts_for_us(0.0).unwrap());
+    assert_eq!(1024, pwm.phase_counts_for_us(4997.1).unwrap());
+    assert_eq!(4095, pwm.phase_counts_for_us(19_984.0).unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_compute_phase_counts_out_of_period() {
+    let pwm = new(&[]);
+    // the period at prescale 30 is about 5079 us
+    assert_invalid_input_data(pwm.phase_counts_for_us(5079.0));
+    assert_invalid_input_data(pwm.phase_counts_for_us(-1.0));
+    assert_invalid_input_data(pwm.phase_counts_for_us(f32::NAN));
+    destroy(pwm);
+}
+
+const MODE1_AWAKE: u8 = MODE1_DEFAULT & !BitFlags::SLEEP;
+
+#[test]
+fn can_set_frequency_verified() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![121]),
+    ];
+    let mut pwm = new(&trans);
+    let hz = pwm
+        .set_frequency_verified(50.0, &mut DelayMock::new())
+        .unwrap();
+    assert!((hz - 50.03).abs() < 0.01);
+    destroy(pwm);
+}
+
+#[test]
+fn set_frequency_verified_keeps_running_device_awake() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![121]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.enable().unwrap();
+    pwm.set_frequency_verified(50.0, &mut DelayMock::new())
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_frequency_verified_detects_mismatch() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![30]),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(
+        Err(Error::VerificationFailed(None)),
+        pwm.set_frequency_verified(50.0, &mut DelayMock::new())
+    );
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_frequency_verified_out_of_range() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_frequency_verified(2000.0, &mut DelayMock::new()));
+    destroy(pwm);
+}
+
+#[test]
+fn default_oscillator_is_internal() {
+    let pwm = new(&[]);
+    assert_eq!(25_000_000, pwm.oscillator_hz());
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_frequency() {
+    let trans = [I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121])];
+    let mut pwm = new(&trans);
+    pwm.set_frequency(50.0).unwrap();
+    assert!((pwm.get_frequency() - 50.03).abs() < 0.01);
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_frequency_out_of_range() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_frequency(2000.0));
+    destroy(pwm);
+}
+
+#[test]
+fn default_frequency() {
+    let pwm = new(&[]);
+    assert!((pwm.get_frequency() - 196.9).abs() < 0.1);
+    destroy(pwm);
+}
+
+#[test]
+fn external_clock_is_used_for_frequency() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT | BitFlags::EXT_CLK],
+        ),
+        // 10 MHz / (4096 * 50 Hz) - 1 = 47.8
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 48]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.use_external_clock(10_000_000).unwrap();
+    pwm.set_frequency(50.0).unwrap();
+    // 10 MHz / (4096 * 49)
+    assert!((pwm.get_frequency() - 49.82).abs() < 0.01);
+    assert!((pwm.pulse_resolution_us() - 4.9).abs() < 0.001);
+    destroy(pwm);
+}
+
+#[test]
+fn failed_external_clock_selection_keeps_oscillator() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT | BitFlags::EXT_CLK],
+        )
+        .with_error(MockError::Io(ErrorKind::Other)),
+    ];
+    let mut pwm = new(&trans);
+    assert!(pwm.use_external_clock(10_000_000).is_err());
+    assert_eq!(25_000_000, pwm.oscillator_hz());
+    destroy(pwm);
+}
+
+#[test]
+fn can_check_external_clock() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![MODE1_DEFAULT]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::MODE1],
+            vec![MODE1_DEFAULT | BitFlags::EXT_CLK],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    assert!(!pwm.is_external_clock().unwrap());
+    assert!(pwm.is_external_clock().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn reset_internal_driver_state_restores_internal_oscillator() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT | BitFlags::EXT_CLK],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.use_external_clock(10_000_000).unwrap();
+    pwm.reset_internal_driver_state();
+    assert_eq!(25_000_000, pwm.oscillator_hz());
+    destroy(pwm);
+}
+
+#[test]
+fn oscillator_frequency_is_used() {
+    // 26 MHz / (4096 * 50 Hz) - 1 = 125.95
+    let trans = [I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 126])];
+    let mut pwm = new(&trans);
+    pwm.set_oscillator_frequency(26_000_000).unwrap();
+    assert_eq!(26_000_000, pwm.oscillator_hz());
+    pwm.set_frequency(50.0).unwrap();
+    assert!((pwm.get_frequency() - 49.98).abs() < 0.01);
+    destroy(pwm);
+}
+
+#[test]
+fn oscillator_frequency_is_kept_after_reset() {
+    let mut pwm = new(&[]);
+    pwm.set_oscillator_frequency(26_000_000).unwrap();
+    pwm.reset_internal_driver_state();
+    assert_eq!(26_000_000, pwm.oscillator_hz());
+    destroy(pwm);
+}
+
+#[test]
+fn oscillator_frequency_does_not_replace_external_clock() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT | BitFlags::EXT_CLK],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.use_external_clock(10_000_000).unwrap();
+    pwm.set_oscillator_frequency(26_000_000).unwrap();
+    assert_eq!(10_000_000, pwm.oscillator_hz());
+    pwm.reset_internal_driver_state();
+    assert_eq!(26_000_000, pwm.oscillator_hz());
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_zero_oscillator_frequency() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_oscillator_frequency(0));
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_period_ns() {
+    // 20 ms -> 50 Hz
+    let trans = [I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121])];
+    let mut pwm = new(&trans);
+    pwm.set_period_ns(20_000_000, &mut DelayMock::new())
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn set_period_ns_restarts_running_oscillator() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.enable().unwrap();
+    pwm.set_period_ns(20_000_000, &mut DelayMock::new())
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_period_ns_out_of_range() {
+    let mut pwm = new(&[]);
+    let mut delay = DelayMock::new();
+    assert_invalid_input_data(pwm.set_period_ns(0, &mut delay));
+    assert_invalid_input_data(pwm.set_period_ns(500_000, &mut delay));
+    assert_invalid_input_data(pwm.set_period_ns(50_000_000, &mut delay));
+    destroy(pwm);
+}
+
+#[test]
+fn can_run_with_frequency() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![30]),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 0, 0x10]),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 30]),
+    ];
+    let mut pwm = new(&trans);
+    let frequency = pwm
+        .with_frequency(50.0, &mut DelayMock::new(), |pwm| {
+            pwm.set_channel_duty_cycle(Channel::C0, 0.0)?;
+            Ok(pwm.get_frequency())
+        })
+        .unwrap();
+    assert!((frequency - 50.0).abs() < 0.5);
+    destroy(pwm);
+}
+
+#[test]
+fn with_frequency_restores_running_oscillator() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![30]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 30]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.enable().unwrap();
+    let result = pwm.with_frequency(50.0, &mut DelayMock::new(), |pwm| {
+        pwm.set_channel_on(Channel::C0, 4096)
+    });
+    assert_invalid_input_data(result);
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_run_with_frequency_out_of_range() {
+    let mut pwm = new(&[]);
+    let result = pwm.with_frequency(2000.0, &mut DelayMock::new(), |_| Ok(()));
+    assert_invalid_input_data(result);
+    destroy(pwm);
+}
+
+#[test]
+fn can_create_with_frequency() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT & !BitFlags::SLEEP],
+        ),
+    ];
+    let mut delay = DelayMock::new();
+    let pwm =
+        Pca9685::new_with_frequency(I2cMock::new(&trans), Address::default(), 50.0, &mut delay)
+            .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_create_with_invalid_frequency() {
+    let mut delay = DelayMock::new();
+    let pwm =
+        Pca9685::new_with_frequency(I2cMock::new(&[]), Address::default(), 2000.0, &mut delay);
+    match pwm {
+        Err((Error::InvalidInputData, mut i2c)) => i2c.done(),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn bus_is_returned_if_creating_with_frequency_fails() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT & !BitFlags::SLEEP],
+        )
+        .with_error(MockError::Io(ErrorKind::Other)),
+    ];
+    let mut delay = DelayMock::new();
+    let pwm =
+        Pca9685::new_with_frequency(I2cMock::new(&trans), Address::default(), 50.0, &mut delay);
+    match pwm {
+        Err((Error::I2C(_), mut i2c)) => i2c.done(),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn configure_preloads_channels_while_asleep() {
+    let mut frame = [0; 32];
+    frame[1] = 0x800;
+    let mut written = vec![Register::C0_ON_L, 0, 0, 0, 0x08];
+    written.resize(65, 0);
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AWAKE]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 121]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, written),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI & !BitFlags::SLEEP]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.enable().unwrap();
+    pwm.set_strict_sleep(true);
+    let mut delay = DelayMock::new();
+    pwm.configure(50.0, &frame, &mut delay).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_configure_invalid_frequency() {
+    let mut pwm = new(&[]);
+    let mut delay = DelayMock::new();
+    assert_invalid_input_data(pwm.configure(2000.0, &[0; 32], &mut delay));
+    destroy(pwm);
+}
@@ -1,9 +1,16 @@
-use embedded_hal_mock::i2c::Transaction as I2cTrans;
-use pwm_pca9685::{DisabledOutputValue, OutputDriver, OutputLogicState, OutputStateChange};
+use embedded_hal_mock::{
+    i2c::{Mock as I2cMock, Transaction as I2cTrans},
+    MockError,
+};
+use pwm_pca9685::{
+    Channel, DisabledOutputValue, Error, OutputConfig, OutputDriver, OutputLogicState,
+    OutputStateChange, Pca9685,
+};
+use std::io::ErrorKind;
 
 mod common;
 use crate::common::{
-    assert_invalid_input_data, destroy, new, BitFlags, Register, DEV_ADDR, MODE1_DEFAULT,
+    assert_invalid_input_data, destroy, new, BitFlags, Register, DEV_ADDR, MODE1_AI, MODE1_DEFAULT,
     MODE2_DEFAULT,
 };
 
@@ -13,6 +20,24 @@ fn can_create_and_destroy() {
     destroy(pwm);
 }
 
+#[test]
+fn debug_shows_cached_configuration() {
+    let pwm = new(&[]);
+    let text = format!("{:?}", pwm);
+    assert!(text.starts_with(
+        "Pca9685 { address: 0x40, mode1: 0b00010001, mode2: 0b00000100, prescale: 30,"
+    ));
+    destroy(pwm);
+}
+
+#[test]
+fn debug_does_not_require_debug_bus() {
+    #[derive(Default)]
+    struct Bus;
+    let pwm: Pca9685<Bus> = Pca9685::default();
+    assert!(format!("{:?}", pwm).contains("address: 0x40"));
+}
+
 call_method_test!(can_enable, enable, MODE1, MODE1_DEFAULT & !BitFlags::SLEEP);
 call_method_test!(can_disable, disable, MODE1, MODE1_DEFAULT);
 call_method_test!(
@@ -30,6 +55,15 @@ call_method_test!(
     OutputLogicState::Inverted
 );
 
+#[test]
+fn can_create_with_default_address() {
+    let trans = [I2cTrans::write(0x40, vec![Register::PRE_SCALE, 3])];
+    let mut pwm = Pca9685::new(I2cMock::new(&trans), Pca9685::DEFAULT_ADDRESS).unwrap();
+    pwm.set_prescale(3).unwrap();
+    destroy(pwm);
+    assert_eq!(0x70, Pca9685::ALL_CALL_ADDRESS);
+}
+
 #[test]
 fn can_use_external_clock() {
     let trans = [
@@ -40,10 +74,18 @@ fn can_use_external_clock() {
         ),
     ];
     let mut pwm = new(&trans);
-    pwm.use_external_clock().unwrap();
+    pwm.use_external_clock(40_000_000).unwrap();
+    assert_eq!(40_000_000, pwm.oscillator_hz());
     destroy(pwm);
 }
 
+invalid_test!(cannot_use_external_clock_zero, use_external_clock, 0);
+invalid_test!(
+    cannot_use_external_clock_too_fast,
+    use_external_clock,
+    50_000_001
+);
+
 invalid_test!(cannot_set_prescale_too_small, set_prescale, 2);
 
 #[test]
@@ -90,6 +132,67 @@ call_method_test!(
     OutputStateChange::OnAck
 );
 
+#[test]
+fn synchronized_output_switches_to_on_stop() {
+    let trans = [
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE2, MODE2_DEFAULT | BitFlags::OCH],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE2, MODE2_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 0, 4]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE2, MODE2_DEFAULT | BitFlags::OCH],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_output_change_behavior(OutputStateChange::OnAck)
+        .unwrap();
+    let value = pwm
+        .with_synchronized_output(|pwm| {
+            pwm.write_channels_from(Channel::C0, &[(0, 1024)])?;
+            Ok(5)
+        })
+        .unwrap();
+    assert_eq!(5, value);
+    destroy(pwm);
+}
+
+#[test]
+fn synchronized_output_keeps_on_stop() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 0, 4]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.with_synchronized_output(|pwm| pwm.write_channels_from(Channel::C0, &[(0, 1024)]))
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn synchronized_output_is_restored_after_error() {
+    let trans = [
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE2, MODE2_DEFAULT | BitFlags::OCH],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE2, MODE2_DEFAULT]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE2, MODE2_DEFAULT | BitFlags::OCH],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_output_change_behavior(OutputStateChange::OnAck)
+        .unwrap();
+    let result = pwm.with_synchronized_output(|pwm| pwm.set_channel_on(Channel::C0, 4096));
+    assert_invalid_input_data(result);
+    destroy(pwm);
+}
+
 call_method_test!(
     can_set_out_driver_totem_pole,
     set_output_driver,
@@ -129,3 +232,422 @@ call_method_test!(
     MODE2_DEFAULT | BitFlags::OUTNE1,
     DisabledOutputValue::HighImpedance
 );
+
+macro_rules! get_output_config_test {
+    ($name:ident, $mode2:expr, $driver:ident, $invert:ident, $change:ident, $disabled:ident) => {
+        #[test]
+        fn $name() {
+            let trans = [I2cTrans::write_read(
+                DEV_ADDR,
+                vec![Register::MODE2],
+                vec![$mode2],
+            )];
+            let mut pwm = new(&trans);
+            let config = pwm.get_output_config().unwrap();
+            assert_eq!(
+                OutputConfig {
+                    driver: OutputDriver::$driver,
+                    invert: OutputLogicState::$invert,
+                    change: OutputStateChange::$change,
+                    disabled_state: DisabledOutputValue::$disabled,
+                },
+                config
+            );
+            destroy(pwm);
+        }
+    };
+}
+
+get_output_config_test!(
+    can_get_default_output_config,
+    MODE2_DEFAULT,
+    TotemPole,
+    Direct,
+    OnStop,
+    Zero
+);
+get_output_config_test!(
+    can_get_output_config_open_drain,
+    MODE2_DEFAULT & !BitFlags::OUT_DRV,
+    OpenDrain,
+    Direct,
+    OnStop,
+    Zero
+);
+get_output_config_test!(
+    can_get_output_config_inverted,
+    MODE2_DEFAULT | BitFlags::INVRT,
+    TotemPole,
+    Inverted,
+    OnStop,
+    Zero
+);
+get_output_config_test!(
+    can_get_output_config_on_ack,
+    MODE2_DEFAULT | BitFlags::OCH,
+    TotemPole,
+    Direct,
+    OnAck,
+    Zero
+);
+get_output_config_test!(
+    can_get_output_config_dis_out_driver,
+    MODE2_DEFAULT | BitFlags::OUTNE0,
+    TotemPole,
+    Direct,
+    OnStop,
+    OutputDriver
+);
+get_output_config_test!(
+    can_get_output_config_dis_high_imp,
+    MODE2_DEFAULT | BitFlags::OUTNE1,
+    TotemPole,
+    Direct,
+    OnStop,
+    HighImpedance
+);
+get_output_config_test!(
+    can_get_output_config_dis_high_imp_both_outne,
+    BitFlags::OUTNE1 | BitFlags::OUTNE0,
+    OpenDrain,
+    Direct,
+    OnStop,
+    HighImpedance
+);
+
+call_method_test!(
+    can_set_default_output_config,
+    set_output_config,
+    MODE2,
+    MODE2_DEFAULT,
+    OutputConfig::default()
+);
+
+call_method_test!(
+    can_set_output_config,
+    set_output_config,
+    MODE2,
+    BitFlags::INVRT | BitFlags::OCH | BitFlags::OUTNE0,
+    OutputConfig {
+        driver: OutputDriver::OpenDrain,
+        invert: OutputLogicState::Inverted,
+        change: OutputStateChange::OnAck,
+        disabled_state: DisabledOutputValue::OutputDriver,
+    }
+);
+
+#[test]
+fn can_check_open_drain() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE2], vec![MODE2_DEFAULT]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::MODE2],
+            vec![MODE2_DEFAULT & !BitFlags::OUT_DRV],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    assert!(!pwm.is_open_drain().unwrap());
+    assert!(pwm.is_open_drain().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn set_output_config_replaces_previous_settings() {
+    let trans = [
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE2, MODE2_DEFAULT | BitFlags::OUTNE1],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE2, BitFlags::OUTNE0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_disabled_output_value(DisabledOutputValue::HighImpedance)
+        .unwrap();
+    pwm.set_output_config(OutputConfig {
+        driver: OutputDriver::OpenDrain,
+        disabled_state: DisabledOutputValue::OutputDriver,
+        ..OutputConfig::default()
+    })
+    .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_reset_to_defaults() {
+    let mut leds = vec![0; 65];
+    leds[0] = Register::C0_ON_L;
+    for i in 0..16 {
+        leds[i * 4 + 4] = 0b0001_0000;
+    }
+    let trans = [
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT & !BitFlags::SLEEP],
+        ),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE2, MODE2_DEFAULT | BitFlags::INVRT],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, leds),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 30]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE2, MODE2_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        // the cached configuration is also reset
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT & !BitFlags::SLEEP],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.enable().unwrap();
+    pwm.set_output_logic_state(OutputLogicState::Inverted)
+        .unwrap();
+    pwm.reset_to_defaults().unwrap();
+    pwm.enable().unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn init_if_needed_applies_defaults_when_sleeping() {
+    let mut leds = vec![0; 65];
+    leds[0] = Register::C0_ON_L;
+    for i in 0..16 {
+        leds[i * 4 + 4] = 0b0001_0000;
+    }
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![MODE1_DEFAULT]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE2], vec![MODE2_DEFAULT]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![30]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, leds),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 30]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE2, MODE2_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+    ];
+    let mut pwm = new(&trans);
+    assert!(pwm.init_if_needed(None).unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn init_if_needed_keeps_external_clock_when_sleeping() {
+    let mut leds = vec![0; 65];
+    leds[0] = Register::C0_ON_L;
+    for i in 0..16 {
+        leds[i * 4 + 4] = 0b0001_0000;
+    }
+    let mode1 = MODE1_DEFAULT | BitFlags::EXT_CLK;
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![mode1]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE2], vec![MODE2_DEFAULT]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![30]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, leds),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 30]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE2, MODE2_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, mode1 & !BitFlags::SLEEP]),
+    ];
+    let mut pwm = new(&trans);
+    assert!(pwm.init_if_needed(Some(10_000_000)).unwrap());
+    assert_eq!(10_000_000, pwm.oscillator_hz());
+    pwm.enable().unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn init_if_needed_preserves_running_configuration() {
+    let mode1 = BitFlags::RESTART | BitFlags::AUTO_INC | BitFlags::ALL_CALL_ADDR;
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![mode1]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE2], vec![BitFlags::OCH]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![121]),
+        // the read configuration is used from then on
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE2, BitFlags::OCH | BitFlags::INVRT],
+        ),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Register::MODE1,
+                mode1 & !BitFlags::RESTART | BitFlags::SLEEP,
+            ],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    assert!(!pwm.init_if_needed(None).unwrap());
+    assert!((pwm.get_frequency() - 50.0).abs() < 0.1);
+    pwm.set_output_logic_state(OutputLogicState::Inverted)
+        .unwrap();
+    pwm.disable().unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn init_if_needed_adopts_external_clock() {
+    let mode1 = BitFlags::AUTO_INC | BitFlags::ALL_CALL_ADDR | BitFlags::EXT_CLK;
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![mode1]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE2], vec![MODE2_DEFAULT]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![48]),
+    ];
+    let mut pwm = new(&trans);
+    assert!(!pwm.init_if_needed(Some(10_000_000)).unwrap());
+    // 10 MHz / (4096 * 49)
+    assert!((pwm.get_frequency() - 49.82).abs() < 0.01);
+    destroy(pwm);
+}
+
+#[test]
+fn init_if_needed_requires_external_clock_frequency() {
+    let mode1 = BitFlags::AUTO_INC | BitFlags::ALL_CALL_ADDR | BitFlags::EXT_CLK;
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![mode1]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE2], vec![MODE2_DEFAULT]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![48]),
+    ];
+    let mut pwm = new(&trans);
+    assert_invalid_input_data(pwm.init_if_needed(None));
+    assert_eq!(25_000_000, pwm.oscillator_hz());
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_init_if_needed_with_invalid_external_clock() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.init_if_needed(Some(0)));
+    assert_invalid_input_data(pwm.init_if_needed(Some(50_000_001)));
+    destroy(pwm);
+}
+
+#[test]
+fn can_get_prescale() {
+    let trans = [I2cTrans::write_read(
+        DEV_ADDR,
+        vec![Register::PRE_SCALE],
+        vec![121],
+    )];
+    let mut pwm = new(&trans);
+    assert_eq!(121, pwm.get_prescale().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_check_default_frequency() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![30]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![121]),
+    ];
+    let mut pwm = new(&trans);
+    assert!(pwm.is_at_default_frequency().unwrap());
+    assert!(!pwm.is_at_default_frequency().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn can_write_raw_register() {
+    let trans = [I2cTrans::write(DEV_ADDR, vec![Register::SUBADDR1, 0xE4])];
+    let mut pwm = new(&trans);
+    pwm.write_raw_register(Register::SUBADDR1, 0xE4).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn write_raw_register_updates_cached_mode1() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        // auto-increment is not enabled again
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], vec![0; 64]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.write_raw_register(Register::MODE1, MODE1_AI).unwrap();
+    pwm.dump_channel_registers().unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_write_test_mode_register() {
+    let mut pwm = new(&[]);
+    assert_error!(pwm.write_raw_register(0xFF, 0), Error::ForbiddenRegister);
+    destroy(pwm);
+}
+
+#[test]
+fn no_error_is_latched_initially() {
+    let pwm = new(&[]);
+    assert!(!pwm.bus_error_occurred());
+    destroy(pwm);
+}
+
+#[test]
+fn bus_error_is_latched_until_cleared() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 30])
+            .with_error(MockError::Io(ErrorKind::Other)),
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 30]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_prescale(30).unwrap_err();
+    pwm.set_prescale(30).unwrap();
+    assert!(pwm.bus_error_occurred());
+    pwm.clear_error();
+    assert!(!pwm.bus_error_occurred());
+    destroy(pwm);
+}
+
+#[test]
+fn read_error_is_latched() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], vec![0; 4])
+            .with_error(MockError::Io(ErrorKind::Other)),
+    ];
+    let mut pwm = new(&trans);
+    pwm.get_effective_pulse(Channel::C0).unwrap_err();
+    assert!(pwm.bus_error_occurred());
+    destroy(pwm);
+}
+
+#[test]
+fn invalid_input_is_not_latched() {
+    let mut pwm = new(&[]);
+    pwm.set_prescale(2).unwrap_err();
+    assert!(!pwm.bus_error_occurred());
+    destroy(pwm);
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn can_describe_config() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![MODE1_DEFAULT]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE2], vec![MODE2_DEFAULT]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![30]),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(
+        "196.9 Hz (prescale 30), totem pole, direct output, change on STOP, disabled output 0, sleeping",
+        pwm.describe_config().unwrap().as_str()
+    );
+    destroy(pwm);
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn can_describe_longest_config() {
+    let mode2 = BitFlags::INVRT | BitFlags::OCH | BitFlags::OUTNE1;
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![0]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE2], vec![mode2]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::PRE_SCALE], vec![3]),
+    ];
+    let mut pwm = new(&trans);
+    assert_eq!(
+        "1525.9 Hz (prescale 3), open-drain, inverted output, change on ACK, disabled output high-impedance, awake",
+        pwm.describe_config().unwrap().as_str()
+    );
+    destroy(pwm);
+}
@@ -0,0 +1,60 @@
+use embedded_hal_mock::{i2c::Transaction as I2cTrans, MockError};
+use pwm_pca9685::{Channel, Ramp, RampShape};
+use std::io::ErrorKind;
+
+mod common;
+use crate::common::{destroy, new, Register, DEV_ADDR, MODE1_AI};
+
+fn pulse(counts: u16) -> I2cTrans {
+    let data = match counts {
+        0 => vec![Register::C0_ON_L, 0, 0, 0, 0x10],
+        4096 => vec![Register::C0_ON_L, 0, 0x10, 0, 0],
+        c => vec![Register::C0_ON_L, 0, 0, c as u8, (c >> 8) as u8],
+    };
+    I2cTrans::write(DEV_ADDR, data)
+}
+
+fn run_ramp(mut ramp: Ramp, expected: &[u16]) {
+    let mut trans = vec![I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI])];
+    trans.extend(expected.iter().map(|c| pulse(*c)));
+    let mut pwm = new(&trans);
+    for counts in expected {
+        assert_eq!(*counts, ramp.value());
+        ramp.step(&mut pwm, Channel::C0).unwrap();
+    }
+    destroy(pwm);
+}
+
+#[test]
+fn triangle_reverses_at_bounds() {
+    let ramp = Ramp::new(0, 10, 4, RampShape::Triangle);
+    run_ramp(ramp, &[0, 4, 8, 10, 6, 2, 0, 4]);
+}
+
+#[test]
+fn sawtooth_wraps_around() {
+    let ramp = Ramp::new(0, 10, 4, RampShape::Sawtooth);
+    run_ramp(ramp, &[0, 4, 8, 10, 0, 4]);
+}
+
+#[test]
+fn bounds_are_limited() {
+    let ramp = Ramp::new(5000, 5000, 0, RampShape::Triangle);
+    run_ramp(ramp, &[4096, 4096]);
+}
+
+#[test]
+fn failed_step_is_repeated() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        pulse(100).with_error(MockError::Io(ErrorKind::Other)),
+        pulse(100),
+        pulse(200),
+    ];
+    let mut pwm = new(&trans);
+    let mut ramp = Ramp::new(100, 200, 100, RampShape::Sawtooth);
+    ramp.step(&mut pwm, Channel::C0).unwrap_err();
+    ramp.step(&mut pwm, Channel::C0).unwrap();
+    ramp.step(&mut pwm, Channel::C0).unwrap();
+    destroy(pwm);
+}
@@ -140,3 +140,94 @@ fn can_disable_then_restart_nonblocking() {
     pwm.restart_nonblocking().unwrap();
     destroy(pwm);
 }
+
+#[test]
+fn can_enable_restart_without_changing_sleep() {
+    let trans = [
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::MODE1, MODE1_DEFAULT & !BitFlags::SLEEP],
+        ),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Register::MODE1,
+                MODE1_DEFAULT & !BitFlags::SLEEP | BitFlags::RESTART,
+            ],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_DEFAULT]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.enable().unwrap();
+    pwm.enable_restart().unwrap();
+    pwm.disable().unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_check_needs_restart() {
+    let trans = [
+        I2cTrans::write_read(DEV_ADDR, vec![Register::MODE1], vec![MODE1_DEFAULT]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::MODE1],
+            vec![MODE1_DEFAULT | BitFlags::RESTART],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    assert!(!pwm.needs_restart().unwrap());
+    assert!(pwm.needs_restart().unwrap());
+    destroy(pwm);
+}
+
+#[test]
+fn service_watchdog_does_nothing_if_restart_not_needed() {
+    let trans = [I2cTrans::write_read(
+        DEV_ADDR,
+        vec![Register::MODE1],
+        vec![MODE1_DEFAULT],
+    )];
+    let mut pwm = new(&trans);
+    pwm.set_frame_retention(true);
+    let mut delay = DelayMock::new();
+    pwm.service_watchdog(&mut delay).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn service_watchdog_restarts_and_reapplies_frame() {
+    let mut frame = [0; 32];
+    frame[1] = 0x123;
+    let mut written = vec![Register::C0_ON_L, 0, 0, 0x23, 0x01];
+    written.resize(65, 0);
+    let mode1 = MODE1_DEFAULT | BitFlags::AUTO_INC;
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, mode1]),
+        I2cTrans::write(DEV_ADDR, written.clone()),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::MODE1],
+            vec![mode1 | BitFlags::RESTART],
+        ),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::MODE1],
+            vec![mode1 | BitFlags::RESTART],
+        ),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, mode1 & !BitFlags::SLEEP]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![
+                Register::MODE1,
+                mode1 & !BitFlags::SLEEP | BitFlags::RESTART,
+            ],
+        ),
+        I2cTrans::write(DEV_ADDR, written),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_frame_retention(true);
+    pwm.push_frame(&frame).unwrap();
+    let mut delay = DelayMock::new();
+    pwm.service_watchdog(&mut delay).unwrap();
+    destroy(pwm);
+}
@@ -0,0 +1,51 @@
+use embedded_hal_mock::{delay::MockNoop as DelayMock, i2c::Transaction as I2cTrans, MockError};
+use std::io::ErrorKind;
+
+mod common;
+use crate::common::{destroy, new, Register, DEV_ADDR, MODE1_AI};
+
+fn snapshot() -> Vec<u8> {
+    let mut data = vec![0; 64];
+    data[2] = 0xFF;
+    data[3] = 0x07;
+    data[63] = 0x10;
+    data
+}
+
+fn self_test_transactions(failing_channel: Option<u8>) -> Vec<I2cTrans> {
+    let mut trans = vec![
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_ON_L], snapshot()),
+        I2cTrans::write(DEV_ADDR, vec![Register::ALL_C_ON_L, 0, 0, 0, 0x10]),
+    ];
+    for channel in 0..16 {
+        let reg = Register::C0_ON_L + channel * 4;
+        if Some(channel) == failing_channel {
+            trans.push(
+                I2cTrans::write(DEV_ADDR, vec![reg, 0, 0x10, 0, 0])
+                    .with_error(MockError::Io(ErrorKind::Other)),
+            );
+            break;
+        }
+        trans.push(I2cTrans::write(DEV_ADDR, vec![reg, 0, 0x10, 0, 0]));
+        trans.push(I2cTrans::write(DEV_ADDR, vec![reg, 0, 0, 0, 0x10]));
+    }
+    let mut restore = vec![Register::C0_ON_L];
+    restore.extend(snapshot());
+    trans.push(I2cTrans::write(DEV_ADDR, restore));
+    trans
+}
+
+#[test]
+fn self_test_lights_each_channel_and_restores_state() {
+    let mut pwm = new(&self_test_transactions(None));
+    pwm.self_test(&mut DelayMock::new(), 100).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn self_test_restores_state_after_error() {
+    let mut pwm = new(&self_test_transactions(Some(3)));
+    pwm.self_test(&mut DelayMock::new(), 100).unwrap_err();
+    destroy(pwm);
+}
@@ -0,0 +1,177 @@
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use pwm_pca9685::{Channel, ServoBank, ServoCalibration};
+
+mod common;
+use crate::common::{assert_invalid_input_data, destroy, new, Register, DEV_ADDR, MODE1_AI};
+
+// At the default prescale of 30 one tick takes 1.24 µs.
+
+fn pulse_trans(off: u16) -> [I2cTrans; 2] {
+    [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C0_ON_L, 0, 0, off as u8, (off >> 8) as u8],
+        ),
+    ]
+}
+
+#[test]
+fn default_calibration() {
+    assert_eq!(
+        ServoCalibration::new(1000.0, 2000.0, 180.0),
+        ServoCalibration::default()
+    );
+}
+
+#[test]
+fn can_set_channel_pulse_us() {
+    let mut pwm = new(&pulse_trans(1210));
+    pwm.set_channel_pulse_us(Channel::C0, 1500.0).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn zero_pulse_sets_full_off() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0, 0, 0, 0x10]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_pulse_us(Channel::C0, 0.0).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_invalid_channel_pulse_us() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_channel_pulse_us(Channel::C0, -1.0));
+    assert_invalid_input_data(pwm.set_channel_pulse_us(Channel::C0, 6000.0));
+    assert_invalid_input_data(pwm.set_channel_pulse_us(Channel::C0, f32::NAN));
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_channel_pulse_us_window() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        // 200 µs: 161, 1200 µs: 968
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 161, 0, 0xC8, 0x03]),
+        // Ends at the end of the period: 3710 + 386
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0x7E, 0x0E, 0, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_pulse_us_window(Channel::C0, 200.0, 1000.0)
+        .unwrap();
+    pwm.set_channel_pulse_us_window(Channel::C0, 4600.0, 479.0)
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn channel_pulse_us_window_does_not_wrap_into_next_period() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::PRE_SCALE, 24]),
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        // 1 µs per tick: starts at 3001 and ends at 4096 instead of 4097
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_ON_L, 0xB9, 0x0B, 0, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_prescale(24).unwrap();
+    pwm.set_channel_pulse_us_window(Channel::C0, 3000.5, 1095.5)
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_invalid_channel_pulse_us_window() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.set_channel_pulse_us_window(Channel::C0, -1.0, 100.0));
+    assert_invalid_input_data(pwm.set_channel_pulse_us_window(Channel::C0, 100.0, -1.0));
+    assert_invalid_input_data(pwm.set_channel_pulse_us_window(Channel::C0, f32::NAN, 100.0));
+    assert_invalid_input_data(pwm.set_channel_pulse_us_window(Channel::C0, 4600.0, 500.0));
+    assert_invalid_input_data(pwm.set_channel_pulse_us_window(Channel::C0, 100.0, 0.0));
+    assert_invalid_input_data(pwm.set_channel_pulse_us_window(Channel::C0, 0.0, 5079.0));
+    destroy(pwm);
+}
+
+#[test]
+fn can_set_servo_angle() {
+    let mut pwm = new(&pulse_trans(1210));
+    pwm.set_servo_angle(Channel::C0, 90.0, ServoCalibration::default())
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_center_servo() {
+    let mut pwm = new(&pulse_trans(1210));
+    pwm.center_servo(Channel::C0, ServoCalibration::default())
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn can_center_asymmetric_servo() {
+    // 1600 µs
+    let mut pwm = new(&pulse_trans(1290));
+    pwm.center_servo(Channel::C0, ServoCalibration::new(700.0, 2500.0, 270.0))
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn cannot_set_servo_angle_out_of_range() {
+    let mut pwm = new(&[]);
+    let cal = ServoCalibration::default();
+    assert_invalid_input_data(pwm.set_servo_angle(Channel::C0, -1.0, cal));
+    assert_invalid_input_data(pwm.set_servo_angle(Channel::C0, 181.0, cal));
+    let cal = ServoCalibration::new(1000.0, 2000.0, 0.0);
+    assert_invalid_input_data(pwm.set_servo_angle(Channel::C0, 0.0, cal));
+    destroy(pwm);
+}
+
+macro_rules! position_byte_test {
+    ($name:ident, $position:expr, $off:expr) => {
+        #[test]
+        fn $name() {
+            let mut pwm = new(&pulse_trans($off));
+            pwm.set_servo_position_byte(Channel::C0, $position, ServoCalibration::default())
+                .unwrap();
+            destroy(pwm);
+        }
+    };
+}
+
+position_byte_test!(position_byte_min, 0, 806);
+position_byte_test!(position_byte_center, 128, 1211);
+position_byte_test!(position_byte_max, 255, 1613);
+
+#[test]
+fn servo_bank_uses_joint_calibration() {
+    let bank = ServoBank::new(ServoCalibration::default())
+        .with_joint(0, Channel::C0)
+        .with_joint_calibration(3, Channel::C0, ServoCalibration::new(700.0, 2500.0, 270.0));
+    let mut pwm = new(&pulse_trans(1210));
+    bank.set_angle(&mut pwm, 0, 90.0).unwrap();
+    destroy(pwm);
+    // 1600 µs
+    let mut pwm = new(&pulse_trans(1290));
+    bank.set_angle(&mut pwm, 3, 135.0).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn servo_bank_rejects_unknown_joint() {
+    let bank = ServoBank::new(ServoCalibration::default())
+        .with_joint(0, Channel::C0)
+        .with_joint(16, Channel::C1);
+    assert_eq!(Some(Channel::C0), bank.joint_channel(0));
+    assert_eq!(None, bank.joint_channel(1));
+    assert_eq!(None, bank.joint_channel(16));
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(bank.set_angle(&mut pwm, 1, 90.0));
+    assert_invalid_input_data(bank.set_angle(&mut pwm, 16, 90.0));
+    assert_invalid_input_data(bank.set_angle(&mut pwm, 0, 200.0));
+    destroy(pwm);
+}
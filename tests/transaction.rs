@@ -0,0 +1,140 @@
+use embedded_hal_mock::i2c::Transaction as I2cTrans;
+use pwm_pca9685::Channel;
+
+mod common;
+use crate::common::{assert_invalid_input_data, destroy, new, Register, DEV_ADDR, MODE1_AI};
+
+#[test]
+fn empty_transaction_does_nothing() {
+    let mut pwm = new(&[]);
+    pwm.transaction(|_| {}).unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn contiguous_changes_are_written_at_once() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C0_OFF_L], vec![0; 6]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C0_OFF_L, 0x23, 0x01, 1, 0, 2, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.transaction(|tx| {
+        tx.set_channel_off(Channel::C0, 0x123);
+        tx.set_channel_on_off(Channel::C1, 1, 2);
+    })
+    .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn unchanged_registers_in_between_are_read_back() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::C0_ON_L],
+            vec![0, 0, 0xAA, 0xBB, 0xCC, 0xDD, 0, 0],
+        ),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C0_ON_L, 5, 0, 0xAA, 0xBB, 0xCC, 0xDD, 7, 0],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.transaction(|tx| {
+        tx.set_channel_off(Channel::C1, 7);
+        tx.set_channel_on(Channel::C0, 5);
+    })
+    .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn full_on_and_off_flags_are_kept() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(
+            DEV_ADDR,
+            vec![Register::C0_ON_L],
+            vec![0, 0x10, 0, 0, 0, 0, 0, 0x10],
+        ),
+        I2cTrans::write(
+            DEV_ADDR,
+            vec![Register::C0_ON_L, 5, 0x10, 0, 0, 0, 0, 7, 0x10],
+        ),
+    ];
+    let mut pwm = new(&trans);
+    pwm.transaction(|tx| {
+        tx.set_channel_on(Channel::C0, 5);
+        tx.set_channel_off(Channel::C1, 7);
+    })
+    .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn on_off_changes_clear_the_flags() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C2_ON_L, 1, 0, 2, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.transaction(|tx| {
+        tx.set_channel_off(Channel::C2, 3);
+        tx.set_channel_on_off(Channel::C2, 1, 2);
+    })
+    .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn later_changes_replace_earlier_ones() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C15_ON_L], vec![0; 4]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C15_ON_L, 1, 0, 3, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.transaction(|tx| {
+        tx.set_channel_on_off(Channel::C15, 1, 2);
+        tx.set_channel_off(Channel::C15, 3);
+    })
+    .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn channel_mapping_is_used() {
+    let trans = [
+        I2cTrans::write(DEV_ADDR, vec![Register::MODE1, MODE1_AI]),
+        I2cTrans::write_read(DEV_ADDR, vec![Register::C15_OFF_L], vec![0; 2]),
+        I2cTrans::write(DEV_ADDR, vec![Register::C15_OFF_L, 3, 0]),
+    ];
+    let mut pwm = new(&trans);
+    pwm.set_channel_mapping([15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0])
+        .unwrap();
+    pwm.transaction(|tx| tx.set_channel_off(Channel::C0, 3))
+        .unwrap();
+    destroy(pwm);
+}
+
+#[test]
+fn nothing_is_written_for_all_channels() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.transaction(|tx| {
+        tx.set_channel_off(Channel::C0, 1);
+        tx.set_channel_off(Channel::All, 1);
+    }));
+    destroy(pwm);
+}
+
+#[test]
+fn nothing_is_written_for_invalid_value() {
+    let mut pwm = new(&[]);
+    assert_invalid_input_data(pwm.transaction(|tx| {
+        tx.set_channel_on(Channel::C0, 1);
+        tx.set_channel_on_off(Channel::C1, 0, 4096);
+    }));
+    destroy(pwm);
+}